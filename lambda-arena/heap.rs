@@ -1,9 +1,28 @@
-use core::{marker::PhantomData, mem::MaybeUninit};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{
+  hash::{Hash, Hasher},
+  marker::PhantomData,
+  mem,
+  mem::MaybeUninit,
+  ops::{Deref, DerefMut},
+  slice,
+};
 
+/// raw index, and the payload of every object. `u16` keeps small heaps
+/// compact, the `u32-index` feature lets a single heap span all of RAM.
+/// `U::MAX` is never an index, objects can use it as a tag.
+#[cfg(not(feature = "u32-index"))]
 pub type U = u16;
+#[cfg(feature = "u32-index")]
+pub type U = u32;
 
 pub struct Idx<T> {
   pub raw : U,
+  /// epoch of the generation `raw` points into when the heap handed this
+  /// out, or 0 for an index made from a raw one, which is not checked.
+  #[cfg(feature = "checked-idx")]
+  epoch : u16,
   _phantom : PhantomData<fn() -> T>,
 }
 
@@ -23,44 +42,417 @@ impl<T> PartialEq for Idx<T> {
 }
 
 impl<T> From<U> for Idx<T> {
-  #[must_use]
   fn from(value : U) -> Idx<T> {
     Idx {
       raw : value,
+      #[cfg(feature = "checked-idx")]
+      epoch : 0,
       _phantom : PhantomData,
     }
   }
 }
 
-pub struct Heap<T, const PAGESIZE: usize, const G1PAGES: usize> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapError {
+  /// the nursery is full and g1 has no room left to promote it into
+  OutOfMemory,
+  /// index to a slot that is not allocated (yet, or anymore)
+  Dangling(U),
+  /// the reserved 0 index
+  Null,
+  /// index handed out before its object was moved or freed by a collection
+  #[cfg(feature = "checked-idx")]
+  Stale(U),
+}
+
+impl core::fmt::Display for HeapError {
+  fn fmt(&self, f : &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      HeapError::OutOfMemory => write!(f, "out of memory"),
+      HeapError::Dangling(raw) => write!(f, "dangling index {raw}"),
+      HeapError::Null => write!(f, "null index"),
+      #[cfg(feature = "checked-idx")]
+      HeapError::Stale(raw) => write!(f, "stale index {raw}, from before a collection"),
+    }
+  }
+}
+
+/// what `Heap::verify` found wrong: `error` for a reference held by the
+/// object at `holder`, or by a root if `holder` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corruption {
+  pub holder : U,
+  pub error : HeapError,
+}
+
+impl core::fmt::Display for Corruption {
+  fn fmt(&self, f : &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self.holder {
+      0 => write!(f, "root holds {}", self.error),
+      holder => write!(f, "{holder} holds {}", self.error),
+    }
+  }
+}
+
+/// FNV-1a, which spreads the hash-consing table. quick on small keys and
+/// needs no allocation, but anyone choosing the keys can make it collide.
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+  fn default() -> Self { Fnv(0xcbf2_9ce4_8422_2325) }
+}
+
+impl Hasher for Fnv {
+  fn finish(&self) -> u64 { self.0 }
+  fn write(&mut self, bytes : &[u8]) {
+    for b in bytes {
+      self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100_0000_01b3);
+    }
+  }
+}
+
+/// a slot on the root stack of a `Heap`, see `Heap::root`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Root(usize);
+
+/// memory of one page, boxed by the heap or handed to `Heap::new_static`.
+enum Page<T : 'static, const PAGESIZE: usize> {
+  #[cfg(feature = "alloc")]
+  Boxed(Box<[T; PAGESIZE]>),
+  Static(&'static mut [T; PAGESIZE]),
+}
+
+impl<T : Default, const PAGESIZE: usize> Page<T, PAGESIZE> {
+  #[cfg(feature = "alloc")]
+  fn boxed() -> Self {
+    let fresh : Box<[T]> = (0..PAGESIZE)
+      .map(|_| T::default())
+      .collect::<Vec<_>>()
+      .into();
+    let Ok(fresh) = fresh.try_into() else {
+      unreachable!()
+    };
+    Page::Boxed(fresh)
+  }
+}
+
+impl<T, const PAGESIZE: usize> Deref for Page<T, PAGESIZE> {
+  type Target = [T; PAGESIZE];
+  fn deref(&self) -> &[T; PAGESIZE] {
+    match self {
+      #[cfg(feature = "alloc")]
+      Page::Boxed(page) => page,
+      Page::Static(page) => page,
+    }
+  }
+}
+
+impl<T, const PAGESIZE: usize> DerefMut for Page<T, PAGESIZE> {
+  fn deref_mut(&mut self) -> &mut [T; PAGESIZE] {
+    match self {
+      #[cfg(feature = "alloc")]
+      Page::Boxed(page) => page,
+      Page::Static(page) => page,
+    }
+  }
+}
+
+/// one of the tables and stacks of a heap: a `Vec`, or a slice handed to
+/// `Heap::new_static` and how much of it is in use. those never grow, `push`
+/// panics when full and `resize` fails.
+enum Buf<X : 'static> {
+  #[cfg(feature = "alloc")]
+  Vec(Vec<X>),
+  Static(&'static mut [X], usize),
+}
+
+impl<X : Copy + Default> Buf<X> {
+  #[cfg(feature = "alloc")]
+  fn zeroed(len : usize) -> Self { Buf::Vec(vec![X::default(); len]) }
+  #[cfg(not(feature = "alloc"))]
+  fn zeroed(len : usize) -> Self { unreachable!("static heaps are handed all {len} of these") }
+}
+
+impl<X : Copy> Buf<X> {
+  /// fails if a static buffer is full.
+  fn push(&mut self, x : X) -> Result<(), HeapError> {
+    match self {
+      #[cfg(feature = "alloc")]
+      Buf::Vec(vec) => vec.push(x),
+      Buf::Static(buf, len) => {
+        if *len >= buf.len() {
+          return Err(HeapError::OutOfMemory);
+        }
+        buf[*len] = x;
+        *len += 1;
+      }
+    }
+    Ok(())
+  }
+  fn pop(&mut self) -> Option<X> {
+    match self {
+      #[cfg(feature = "alloc")]
+      Buf::Vec(vec) => vec.pop(),
+      Buf::Static(buf, len) => {
+        *len = len.checked_sub(1)?;
+        Some(buf[*len])
+      }
+    }
+  }
+  fn truncate(&mut self, to : usize) {
+    match self {
+      #[cfg(feature = "alloc")]
+      Buf::Vec(vec) => vec.truncate(to),
+      Buf::Static(_, len) => *len = to.min(*len),
+    }
+  }
+  fn clear(&mut self) { self.truncate(0); }
+  /// set the length to `to`, filling up with `x`. fails if that does not fit.
+  fn resize(&mut self, to : usize, x : X) -> bool {
+    match self {
+      #[cfg(feature = "alloc")]
+      Buf::Vec(vec) => vec.resize(to, x),
+      Buf::Static(buf, len) => {
+        if to > buf.len() {
+          return false;
+        }
+        if to > *len {
+          buf[*len..to].fill(x);
+        }
+        *len = to;
+      }
+    }
+    true
+  }
+}
+
+impl<X> Default for Buf<X> {
+  #[cfg(feature = "alloc")]
+  fn default() -> Self { Buf::Vec(Vec::new()) }
+  #[cfg(not(feature = "alloc"))]
+  fn default() -> Self { Buf::Static(&mut [], 0) }
+}
+
+impl<X> Deref for Buf<X> {
+  type Target = [X];
+  fn deref(&self) -> &[X] {
+    match self {
+      #[cfg(feature = "alloc")]
+      Buf::Vec(vec) => vec,
+      Buf::Static(buf, len) => &buf[..*len],
+    }
+  }
+}
+
+impl<X> DerefMut for Buf<X> {
+  fn deref_mut(&mut self) -> &mut [X] {
+    match self {
+      #[cfg(feature = "alloc")]
+      Buf::Vec(vec) => vec,
+      Buf::Static(buf, len) => &mut buf[..*len],
+    }
+  }
+}
+
+/// everything a heap would otherwise allocate, for `Heap::new_static`. none
+/// of it has to be initialized, so it can be a `static` of `MaybeUninit` or
+/// a region the linker script sets aside.
+pub struct Memory<T : 'static, const PAGESIZE: usize> {
+  /// g2
+  pub nursery : &'static mut MaybeUninit<[T; PAGESIZE]>,
+  /// g1, at least `G1PAGES` pages
+  pub pages : &'static mut [MaybeUninit<[T; PAGESIZE]>],
+  /// the root stack, which also holds the work stacks of traversals and of
+  /// `collect_g1`
+  pub roots : &'static mut [MaybeUninit<(Idx<T>, U)>],
+  /// mark bits, one per slot: `(G1PAGES + 1) * PAGESIZE / 32` rounded up
+  pub marks : &'static mut [MaybeUninit<u32>],
+  /// objects `collect_step` still has to trace, at most one per g1 slot
+  pub gray : &'static mut [MaybeUninit<U>],
+  /// the hash-consing table and its spare, of the same length. `cons` runs
+  /// out of memory once the table would have to grow past that.
+  pub conses : [&'static mut [MaybeUninit<U>]; 2],
+}
+
+/// write `f()` all over `memory`.
+fn fill<X>(memory : &'static mut [MaybeUninit<X>], f : impl Fn() -> X) -> &'static mut [X] {
+  for x in memory.iter_mut() {
+    x.write(f());
+  }
+  // SAFETY: all of it is initialized now
+  unsafe { &mut *(memory as *mut [MaybeUninit<X>] as *mut [X]) }
+}
+
+/// `fill` for pages, a slot at a time rather than a page on the stack.
+fn fill_pages<T : Default, const PAGESIZE: usize>(
+  memory : &'static mut [MaybeUninit<[T; PAGESIZE]>],
+) -> &'static mut [[T; PAGESIZE]] {
+  let (ptr, len) = (memory.as_mut_ptr(), memory.len());
+  // SAFETY: an array is laid out as its elements one after the other
+  fill(
+    unsafe { slice::from_raw_parts_mut(ptr.cast(), len * PAGESIZE) },
+    T::default,
+  );
+  unsafe { slice::from_raw_parts_mut(ptr.cast(), len) }
+}
+
+pub struct Heap<T : 'static, const PAGESIZE: usize, const G1PAGES: usize> {
   g2_ptr : usize,
   page_ptr : usize,
-  g1_pages : [Option<Box<[T; PAGESIZE]>>; G1PAGES],
-  g2_page : [T; PAGESIZE],
+  /// next free offset in `g1_pages[page_ptr]`
+  g1_ptr : usize,
+  g1_pages : [Option<Page<T, PAGESIZE>>; G1PAGES],
+  /// static memory for each g1 page while it is not in `g1_pages`
+  reserve : [Option<&'static mut [T; PAGESIZE]>; G1PAGES],
+  g2_page : Page<T, PAGESIZE>,
+  /// forwarding addresses of g2 objects during a minor collection, 0 if not
+  /// yet copied.
+  g2_fwd : [U; PAGESIZE],
+  /// indices the caller holds on to, kept alive and updated by collections.
+  /// each carries a tag the collector ignores, so traversals can keep their
+  /// work stack here instead of on the call stack.
+  roots : Buf<(Idx<T>, U)>,
+  /// open addressing table of the raw indices of hash-consed objects, by
+  /// content. empty until the first `cons`.
+  conses : Buf<U>,
+  /// as long as `conses`, collections rebuild the table into it so they do
+  /// not need to allocate.
+  conses_spare : Buf<U>,
+  /// number of entries in `conses`
+  consed : usize,
+  /// g1 slots written since the last minor collection, which may point into
+  /// g2. that collection treats them as roots.
+  remembered : [U; REMEMBERED],
+  remembered_len : usize,
+  /// `remembered` ran over, the next minor collection scans all of g1.
+  remembered_all : bool,
+  /// mark bits of a major collection, one per slot. allocated by the first
+  /// one and kept around, all clear in between.
+  marks : Buf<u32>,
+  /// where the incremental major collection is at, see `collect_step`.
+  phase : Phase,
+  /// marked g1 objects whose references are not traced yet
+  gray : Buf<U>,
+  /// g1 slots freed by `collect_step`, linked through `Object::moved`.
+  /// `alloc_g1` takes from here before bumping.
+  free_head : U,
+  free_len : usize,
+  /// g2 offsets whose copies went into free slots, which the cheney scan
+  /// does not reach. linked through the emptied g2 slots.
+  unscanned : U,
+  /// bumped whenever a collection moves objects in g1 or g2, see
+  /// `Idx::epoch`. the sweep of `collect_step` moves nothing and only frees
+  /// what no index can still be live for, so it leaves `g1_epoch` alone.
+  #[cfg(feature = "checked-idx")]
+  g1_epoch : u16,
+  #[cfg(feature = "checked-idx")]
+  g2_epoch : u16,
+  /// `shade_refs` while marking. kept here for `get_mut`, which does not
+  /// know `T : Object`.
+  barrier : Option<Barrier<Self, T>>,
 }
 
+/// see `Heap::barrier`
+type Barrier<H, T> = fn(&mut H, Idx<T>) -> Result<(), HeapError>;
+
+/// progress of the incremental major collection, see `Heap::collect_step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+  Idle,
+  /// tracing from the roots as they were when the cycle started
+  Mark,
+  /// freeing the unmarked g1 slots before `end`, `at` is the next one. both
+  /// count g1 slots from 0 in allocation order, skipping offset 0 of every
+  /// page.
+  Sweep {
+    at : usize,
+    end : usize,
+  },
+}
+
+/// the next epoch, 0 being reserved for unchecked indices.
+#[cfg(feature = "checked-idx")]
+fn next_epoch(epoch : &mut u16) { *epoch = epoch.wrapping_add(1).max(1); }
+
+fn marked(marks : &[u32], raw : U) -> bool { marks[raw as usize / 32] & 1 << (raw % 32) != 0 }
+fn set_mark(marks : &mut [u32], raw : U) { marks[raw as usize / 32] |= 1 << (raw % 32); }
+
+/// capacity of the remembered set, beyond that a minor collection falls
+/// back to scanning all of g1.
+const REMEMBERED : usize = 32;
+
 impl<T, const PAGESIZE: usize, const G1PAGES: usize> Heap<T, PAGESIZE, G1PAGES>
 where
   T : Default,
 {
+  /// a heap that boxes its pages as it needs them, and grows its tables.
+  #[cfg(feature = "alloc")]
   #[must_use]
-  pub fn new() -> Heap<T, PAGESIZE, G1PAGES> {
-    let mut g1_pages : [MaybeUninit<Option<Box<[T; PAGESIZE]>>>; G1PAGES] =
-      MaybeUninit::uninit_array();
-    for p in g1_pages.iter_mut() {
-      p.write(None);
-    }
-    let g1_pages = unsafe { MaybeUninit::array_assume_init(g1_pages) };
-    let mut g2_page : [MaybeUninit<T>; PAGESIZE] = MaybeUninit::uninit_array();
-    for p in g2_page.iter_mut() {
-      p.write(Default::default());
-    }
-    let g2_page = unsafe { MaybeUninit::array_assume_init(g2_page) };
+  pub fn new() -> Heap<T, PAGESIZE, G1PAGES> { Self::with_nursery(Page::boxed()) }
+  /// a heap entirely in `memory`, which never allocates. where a `new` heap
+  /// would grow its root and gray stacks or its hash-consing table instead,
+  /// it runs out of memory.
+  #[must_use]
+  pub fn new_static(memory : Memory<T, PAGESIZE>) -> Heap<T, PAGESIZE, G1PAGES> {
+    let Memory {
+      nursery,
+      pages,
+      roots,
+      marks,
+      gray,
+      conses: [conses, conses_spare],
+    } = memory;
+    assert!(pages.len() >= G1PAGES, "fewer pages than G1PAGES");
+    assert!(
+      marks.len() * 32 >= (G1PAGES + 1) * PAGESIZE,
+      "fewer mark bits than slots"
+    );
+    assert_eq!(conses.len(), conses_spare.len());
+    let mut heap = Self::with_nursery(Page::Static(&mut fill_pages(slice::from_mut(nursery))[0]));
+    for (reserve, page) in heap.reserve.iter_mut().zip(fill_pages(pages)) {
+      *reserve = Some(page);
+    }
+    heap.roots = Buf::Static(fill(roots, || (0.into(), 0)), 0);
+    let marks = fill(marks, || 0);
+    heap.marks = Buf::Static(marks, marks.len());
+    heap.gray = Buf::Static(fill(gray, || 0), 0);
+    heap.conses = Buf::Static(fill(conses, || 0), 0);
+    heap.conses_spare = Buf::Static(fill(conses_spare, || 0), 0);
+    heap
+  }
+  fn with_nursery(g2_page : Page<T, PAGESIZE>) -> Heap<T, PAGESIZE, G1PAGES> {
+    assert!(
+      (G1PAGES + 1) * PAGESIZE - 1 <= U::MAX as usize,
+      "heap too large for its index type"
+    );
+    let g1_pages = core::array::from_fn(|_| None);
+    let reserve = core::array::from_fn(|_| None);
     Heap {
       g2_ptr : 1,
       page_ptr : 0,
+      g1_ptr : 1,
       g1_pages,
+      reserve,
       g2_page,
+      g2_fwd : [0; PAGESIZE],
+      roots : Buf::default(),
+      conses : Buf::default(),
+      conses_spare : Buf::default(),
+      consed : 0,
+      remembered : [0; REMEMBERED],
+      remembered_len : 0,
+      remembered_all : false,
+      marks : Buf::default(),
+      phase : Phase::Idle,
+      gray : Buf::default(),
+      free_head : 0,
+      free_len : 0,
+      unscanned : 0,
+      #[cfg(feature = "checked-idx")]
+      g1_epoch : 1,
+      #[cfg(feature = "checked-idx")]
+      g2_epoch : 1,
+      barrier : None,
     }
   }
   fn address(idx : Idx<T>) -> (usize, usize) {
@@ -71,56 +463,827 @@ where
     assert_ne!(offset, 0);
     Idx::from((page * PAGESIZE + offset) as U)
   }
+  /// the g1 slot numbered `pos`, counting from 0 in allocation order
+  fn g1_at(pos : usize) -> Idx<T> {
+    Self::unaddress_g1(pos / (PAGESIZE - 1), pos % (PAGESIZE - 1) + 1)
+  }
+  /// number of the next g1 slot to bump allocate
+  fn g1_pos(&self) -> usize { self.page_ptr * (PAGESIZE - 1) + self.g1_ptr - 1 }
   fn unaddress_g2(offset : usize) -> Idx<T> {
     assert_ne!(offset, 0);
     Idx::from((G1PAGES * PAGESIZE + offset) as U)
   }
-  #[must_use]
-  pub fn get(&self, idx : Idx<T>) -> &T {
+  /// page and offset of `idx`, if it points at an allocated slot.
+  fn locate(&self, idx : Idx<T>) -> Result<(usize, usize), HeapError> {
+    if idx == 0.into() {
+      return Err(HeapError::Null);
+    }
     let (page, offset) = Self::address(idx);
+    #[cfg(feature = "checked-idx")]
+    if idx.epoch != 0 && idx.epoch != self.epoch(page) {
+      return Err(HeapError::Stale(idx.raw));
+    }
+    let allocated = if page == G1PAGES {
+      offset < self.g2_ptr
+    } else {
+      page < self.page_ptr || page == self.page_ptr && offset < self.g1_ptr
+    };
+    if allocated && offset != 0 {
+      Ok((page, offset))
+    } else {
+      Err(HeapError::Dangling(idx.raw))
+    }
+  }
+  pub fn get(&self, idx : Idx<T>) -> Result<&T, HeapError> {
+    let (page, offset) = self.locate(idx)?;
     if page == G1PAGES {
-      &self.g2_page[offset]
-    } else if page < G1PAGES {
-      &self.g1_pages[page].as_ref().unwrap()[offset]
+      Ok(&self.g2_page[offset])
     } else {
-      panic!("segmentation fault lol")
+      let page = self.g1_pages[page]
+        .as_ref()
+        .ok_or(HeapError::Dangling(idx.raw))?;
+      Ok(&page[offset])
     }
   }
-  pub fn get_mut(&mut self, idx : Idx<T>) -> &mut T {
-    let (page, offset) = Self::address(idx);
+  /// the slot gets remembered if it is in g1, as anything could be written
+  /// into it. prefer `set` where that works, it only remembers slots that do
+  /// end up pointing into g2.
+  pub fn get_mut(&mut self, idx : Idx<T>) -> Result<&mut T, HeapError> {
+    if self.locate(idx)?.0 != G1PAGES {
+      self.remember(idx);
+    }
+    if let Some(barrier) = self.barrier {
+      barrier(self, idx)?;
+    }
+    self.get_mut_(idx)
+  }
+  fn get_mut_(&mut self, idx : Idx<T>) -> Result<&mut T, HeapError> {
+    let (page, offset) = self.locate(idx)?;
     if page == G1PAGES {
-      &mut self.g2_page[offset]
-    } else if page < G1PAGES {
-      &mut self.g1_pages[page].as_mut().unwrap()[offset]
+      Ok(&mut self.g2_page[offset])
+    } else {
+      let page = self.g1_pages[page]
+        .as_mut()
+        .ok_or(HeapError::Dangling(idx.raw))?;
+      Ok(&mut page[offset])
+    }
+  }
+  /// note that the g1 slot `idx` may point into g2 now.
+  fn remember(&mut self, idx : Idx<T>) {
+    if self.remembered_all || self.remembered[..self.remembered_len].contains(&idx.raw) {
+      return;
+    }
+    if self.remembered_len == REMEMBERED {
+      self.remembered_all = true;
     } else {
-      panic!("segmentation fault lol")
+      self.remembered[self.remembered_len] = idx.raw;
+      self.remembered_len += 1;
     }
   }
-  /// allocate in g2 unconditionally. only call this after checking for
-  /// high water mark and collect g2 if needed.
-  pub fn alloc_g2(&mut self) -> Idx<T> {
-    assert!(self.g2_ptr < PAGESIZE);
+  /// allocate in g2 without collecting. check for high water mark
+  /// (`g2_full`) and `collect_g2` first if it should not fail.
+  pub fn alloc_g2(&mut self) -> Result<Idx<T>, HeapError> {
+    if self.g2_full() {
+      return Err(HeapError::OutOfMemory);
+    }
     let newaddr = self.g2_ptr;
     self.g2_ptr += 1;
-    Self::unaddress_g2(newaddr)
+    Ok(self.stamp(Self::unaddress_g2(newaddr)))
+  }
+  #[cfg(feature = "checked-idx")]
+  fn epoch(&self, page : usize) -> u16 {
+    if page == G1PAGES {
+      self.g2_epoch
+    } else {
+      self.g1_epoch
+    }
+  }
+  /// `idx` tagged with the current epoch of its generation, for handing out.
+  fn stamp(&self, idx : Idx<T>) -> Idx<T> {
+    #[cfg(feature = "checked-idx")]
+    if idx != 0.into() {
+      return Idx {
+        epoch : self.epoch(Self::address(idx).0),
+        ..idx
+      };
+    }
+    idx
+  }
+
+  /// push `idx` on the root stack. collections treat it as live and update
+  /// it when the object moves, read it back with `rooted`.
+  pub fn root(&mut self, idx : Idx<T>) -> Result<Root, HeapError> { self.push_frame(idx, 0) }
+  #[must_use]
+  pub fn rooted(&self, root : Root) -> Idx<T> { self.stamp(self.roots[root.0].0) }
+  /// roots are kept untagged, collections keep them up to date.
+  pub fn set_root(&mut self, root : Root, idx : Idx<T>) { self.roots[root.0].0 = idx.raw.into(); }
+  /// `root` with a tag. a null `idx` is fine, the frame then only carries
+  /// `tag`. both fail once a static root stack is full.
+  pub fn push_frame(&mut self, idx : Idx<T>, tag : U) -> Result<Root, HeapError> {
+    self.roots.push((idx.raw.into(), tag))?;
+    Ok(Root(self.roots.len() - 1))
+  }
+  /// pop the topmost root and its tag, unless that would go below `base`.
+  pub fn pop_frame(&mut self, base : Root) -> Option<(Idx<T>, U)> {
+    if self.roots.len() > base.0 {
+      self.roots.pop().map(|(idx, tag)| (self.stamp(idx), tag))
+    } else {
+      None
+    }
+  }
+  /// the `Root` the next call to `root` will return. `unroot` it to drop
+  /// everything rooted from here on.
+  #[must_use]
+  pub fn root_mark(&self) -> Root { Root(self.roots.len()) }
+  /// pop `root` and everything rooted after it.
+  pub fn unroot(&mut self, root : Root) { self.roots.truncate(root.0); }
+
+  /// end of the part of g2 that gets allocated: all of it, unless the last
+  /// slot would be at `U::MAX`.
+  const G2_END : usize = if (G1PAGES + 1) * PAGESIZE - 1 == U::MAX as usize {
+    PAGESIZE - 1
+  } else {
+    PAGESIZE
+  };
+  #[must_use]
+  pub fn g2_full(&self) -> bool { self.g2_ptr >= Self::G2_END }
+
+  /// number of slots g1 can still hand out, counting pages not boxed yet
+  /// and the free list.
+  #[must_use]
+  pub fn g1_free(&self) -> usize {
+    let bump = if self.page_ptr < G1PAGES {
+      (PAGESIZE - self.g1_ptr) + (G1PAGES - self.page_ptr - 1) * (PAGESIZE - 1)
+    } else {
+      0
+    };
+    bump + self.free_len
+  }
+  #[must_use]
+  pub fn phase(&self) -> Phase { self.phase }
+
+  /// write out fill levels, the root stack and every allocated slot, for
+  /// looking at a heap while debugging.
+  pub fn dump(&self, out : &mut impl core::fmt::Write) -> core::fmt::Result
+  where
+    T : core::fmt::Debug,
+  {
+    writeln!(out, "g2: {}/{} used", self.g2_ptr - 1, PAGESIZE - 1)?;
+    writeln!(
+      out,
+      "g1: page {}/{} at {}, {} free",
+      self.page_ptr,
+      G1PAGES,
+      self.g1_ptr,
+      self.g1_free()
+    )?;
+    write!(out, "roots:")?;
+    for (root, tag) in self.roots.iter() {
+      write!(out, " {}:{tag}", root.raw)?;
+    }
+    writeln!(out)?;
+    for page in 0..G1PAGES {
+      let end = match page.cmp(&self.page_ptr) {
+        core::cmp::Ordering::Less => PAGESIZE,
+        core::cmp::Ordering::Equal => self.g1_ptr,
+        core::cmp::Ordering::Greater => break,
+      };
+      if self.g1_pages[page].is_none() {
+        break;
+      }
+      writeln!(out, "page {page}:")?;
+      for offset in 1..end {
+        let idx = Self::unaddress_g1(page, offset);
+        writeln!(
+          out,
+          "  {}: {:?}",
+          idx.raw,
+          self.g1_pages[page].as_ref().unwrap()[offset]
+        )?;
+      }
+    }
+    writeln!(out, "g2:")?;
+    for offset in 1..self.g2_ptr {
+      let idx = Self::unaddress_g2(offset);
+      writeln!(out, "  {}: {:?}", idx.raw, self.g2_page[offset])?;
+    }
+    Ok(())
+  }
+
+  /// give g1 page `page` its memory, from `reserve` if there is some.
+  fn box_page(&mut self, page : usize) {
+    self.g1_pages[page] = Some(match self.reserve[page].take() {
+      Some(memory) => Page::Static(memory),
+      #[cfg(feature = "alloc")]
+      None => Page::boxed(),
+      #[cfg(not(feature = "alloc"))]
+      None => unreachable!("static heaps have memory for all of g1"),
+    });
+  }
+  /// take back the memory of g1 page `page`, returning whether it had any.
+  fn unbox_page(&mut self, page : usize) -> bool {
+    match self.g1_pages[page].take() {
+      Some(Page::Static(memory)) => self.reserve[page] = Some(memory),
+      #[cfg(feature = "alloc")]
+      Some(Page::Boxed(_)) => {}
+      None => return false,
+    }
+    true
+  }
+
+  fn g1_slot(&mut self, page : usize, offset : usize) -> &mut T {
+    &mut self.g1_pages[page].as_mut().expect("unboxed g1 page")[offset]
+  }
+  /// the slot at `idx`, allocated or not
+  fn slot(&self, idx : Idx<T>) -> &T {
+    match Self::address(idx) {
+      (page, offset) if page == G1PAGES => &self.g2_page[offset],
+      (page, offset) => &self.g1_pages[page].as_ref().expect("unboxed g1 page")[offset],
+    }
+  }
+  fn slot_mut(&mut self, idx : Idx<T>) -> &mut T {
+    match Self::address(idx) {
+      (page, offset) if page == G1PAGES => &mut self.g2_page[offset],
+      (page, offset) => self.g1_slot(page, offset),
+    }
   }
+}
 
-  pub fn init(&mut self, init : T) -> Option<Idx<T>> {
-    let new = self.alloc_g2();
-    *self.get_mut(new) = init;
-    Some(new)
+impl<T, const PAGESIZE: usize, const G1PAGES: usize> Heap<T, PAGESIZE, G1PAGES>
+where
+  T : Default + Object,
+{
+  /// a g1 slot off the free list, or else bump allocated in the current g1
+  /// page, boxing a fresh page when it runs out. callers make sure `g1_free`
+  /// is non-zero. objects allocated while marking start out marked.
+  fn alloc_g1(&mut self) -> Idx<T> {
+    let new = if self.free_head != 0 {
+      let new = Idx::from(self.free_head);
+      self.free_head = self.slot(new).moved_to().raw;
+      self.free_len -= 1;
+      new
+    } else {
+      if self.g1_ptr >= PAGESIZE {
+        self.page_ptr += 1;
+        self.g1_ptr = 1;
+      }
+      assert!(self.page_ptr < G1PAGES, "g1 overrun");
+      if self.g1_pages[self.page_ptr].is_none() {
+        self.box_page(self.page_ptr);
+      }
+      let newaddr = self.g1_ptr;
+      self.g1_ptr += 1;
+      Self::unaddress_g1(self.page_ptr, newaddr)
+    };
+    if self.phase == Phase::Mark {
+      set_mark(&mut self.marks, new.raw);
+    }
+    new
+  }
+
+  /// copy a g2 object into g1 unless it already has been, and return its new
+  /// address. anything outside of g2 stays where it is.
+  fn forward(&mut self, idx : Idx<T>) -> Idx<T> {
+    let (page, offset) = Self::address(idx);
+    if page != G1PAGES {
+      return idx;
+    }
+    if self.g2_fwd[offset] != 0 {
+      return self.g2_fwd[offset].into();
+    }
+    let off_free_list = self.free_head != 0;
+    let new = self.alloc_g1();
+    let (page, new_offset) = Self::address(new);
+    *self.g1_slot(page, new_offset) = mem::take(&mut self.g2_page[offset]);
+    self.g2_fwd[offset] = new.raw;
+    if off_free_list {
+      self.g2_page[offset] = T::moved(self.unscanned.into());
+      self.unscanned = offset as U;
+    }
+    new
+  }
+
+  /// minor collection: copy everything in g2 reachable from the root stack
+  /// and `extra` into g1 (cheney style, the promoted objects are the scan
+  /// queue), rewrite the roots to the new addresses and empty g2.
+  ///
+  /// of g1, only the slots remembered by `set` and `get_mut` are scanned, so
+  /// old objects must not be written any other way.
+  ///
+  /// fails without touching anything if g1 could not take the whole nursery,
+  /// a half finished collection would leave dangling indices behind.
+  pub fn collect_g2(&mut self, extra : &mut [Idx<T>]) -> Result<(), HeapError> {
+    self.collect_g2_(extra, None)
+  }
+  fn collect_g2_(
+    &mut self,
+    extra : &mut [Idx<T>],
+    value : Option<&mut T>,
+  ) -> Result<(), HeapError> {
+    if self.g1_free() < self.g2_ptr - 1 {
+      return Err(HeapError::OutOfMemory);
+    }
+    let (mut scan_page, mut scan_offset) = (self.page_ptr, self.g1_ptr);
+    let mut roots = mem::take(&mut self.roots);
+    for root in roots.iter_mut().map(|(r, _)| r).chain(extra.iter_mut()) {
+      if *root != 0.into() {
+        *root = self.forward(*root);
+      }
+    }
+    self.roots = roots;
+    if let Some(value) = value {
+      value.map_refs(&mut |r| if r == 0.into() { r } else { self.forward(r) });
+    }
+    if self.remembered_all {
+      let (mut page, mut offset) = (0, 1);
+      while (page, offset) != (scan_page, scan_offset) {
+        if offset >= PAGESIZE {
+          page += 1;
+          offset = 1;
+          continue;
+        }
+        self.forward_slot(page, offset);
+        offset += 1;
+      }
+    } else {
+      let remembered = self.remembered;
+      for &raw in &remembered[..self.remembered_len] {
+        let (page, offset) = Self::address(raw.into());
+        self.forward_slot(page, offset);
+      }
+    }
+    (self.remembered_len, self.remembered_all) = (0, false);
+    loop {
+      while (scan_page, scan_offset) != (self.page_ptr, self.g1_ptr) {
+        if scan_offset >= PAGESIZE {
+          scan_page += 1;
+          scan_offset = 1;
+          continue;
+        }
+        self.forward_slot(scan_page, scan_offset);
+        scan_offset += 1;
+      }
+      if self.unscanned == 0 {
+        break;
+      }
+      let offset = self.unscanned as usize;
+      self.unscanned = self.g2_page[offset].moved_to().raw;
+      let (page, offset) = Self::address(self.g2_fwd[offset].into());
+      self.forward_slot(page, offset);
+    }
+    if !self.conses.is_empty() {
+      self.rehash_conses(|heap, raw| match Self::address(raw.into()) {
+        (page, offset) if page == G1PAGES => heap.g2_fwd[offset],
+        _ => raw,
+      });
+    }
+    self.g2_fwd[1..self.g2_ptr].fill(0);
+    self.g2_ptr = 1;
+    #[cfg(feature = "checked-idx")]
+    next_epoch(&mut self.g2_epoch);
+    Ok(())
+  }
+
+  /// major collection: mark everything reachable from the root stack and
+  /// `extra`, then compact g1 with two fingers, the topmost live objects
+  /// filling the lowest holes, and give the pages left empty back to the
+  /// allocator. returns how many pages that was.
+  ///
+  /// g2 is traced but not moved, so this works when g1 is too full for a
+  /// minor collection. `init` runs it by itself in that case.
+  ///
+  /// fails without moving anything if a static root stack is too small for
+  /// the marking.
+  pub fn collect_g1(&mut self, extra : &mut [Idx<T>]) -> Result<usize, HeapError> {
+    self.collect_g1_(extra, None)
+  }
+  fn collect_g1_(
+    &mut self,
+    extra : &mut [Idx<T>],
+    mut value : Option<&mut T>,
+  ) -> Result<usize, HeapError> {
+    // whatever `collect_step` did so far is redone here
+    self.abandon_step();
+    let mut marks = mem::take(&mut self.marks);
+    if marks.is_empty() {
+      marks = Buf::zeroed(((G1PAGES + 1) * PAGESIZE).div_ceil(32));
+    }
+
+    // mark, with the work stack on top of the root stack
+    let base = self.root_mark();
+    if let Err(e) = self.mark(&mut marks, base, extra, value.as_deref()) {
+      self.unroot(base);
+      marks.fill(0);
+      self.marks = marks;
+      return Err(e);
+    }
+    for cons in self.conses.iter_mut() {
+      if *cons != 0 && !marked(&marks, *cons) {
+        *cons = 0;
+      }
+    }
+
+    // compact. g1 slots are numbered from 0 in allocation order, skipping
+    // offset 0 of every page. once `free` meets `live`, everything below is
+    // live and everything marked above has moved.
+    // free slots are holes like any other, the free list goes
+    (self.free_head, self.free_len) = (0, 0);
+    let raw = |pos : usize| Self::g1_at(pos).raw;
+    let (mut free, mut live) = (0, self.g1_pos());
+    loop {
+      while free < live && marked(&marks, raw(free)) {
+        free += 1;
+      }
+      while live > free && !marked(&marks, raw(live - 1)) {
+        live -= 1;
+      }
+      if free >= live {
+        break;
+      }
+      let (to, from) = (raw(free), raw(live - 1));
+      let obj = mem::replace(self.slot_mut(from.into()), T::moved(to.into()));
+      *self.slot_mut(to.into()) = obj;
+      set_mark(&mut marks, to);
+      free += 1;
+      live -= 1;
+    }
+    let kept = free;
+    let fwd = |heap : &Self, r : Idx<T>| {
+      if r == 0.into() {
+        return r;
+      }
+      match Self::address(r) {
+        (page, offset) if page != G1PAGES && page * (PAGESIZE - 1) + offset > kept => {
+          heap.slot(r).moved_to()
+        }
+        _ => r,
+      }
+    };
+
+    // point everything at the new addresses
+    let g2_marked = (1..self.g2_ptr)
+      .map(Self::unaddress_g2)
+      .filter(|i| marked(&marks, i.raw));
+    for idx in (0..kept).map(|pos| raw(pos).into()).chain(g2_marked) {
+      let mut obj = mem::take(self.slot_mut(idx));
+      obj.map_refs(&mut |r| fwd(self, r));
+      *self.slot_mut(idx) = obj;
+    }
+    for i in 0..self.roots.len() {
+      self.roots[i].0 = fwd(self, self.roots[i].0);
+    }
+    for r in extra.iter_mut() {
+      *r = fwd(self, *r);
+    }
+    if let Some(value) = &mut value {
+      value.map_refs(&mut |r| fwd(self, r));
+    }
+    let (remembered, len) = (self.remembered, mem::take(&mut self.remembered_len));
+    for &r in &remembered[..len] {
+      if marked(&marks, r) {
+        self.remember(fwd(self, r.into()));
+      }
+    }
+    if !self.conses.is_empty() {
+      self.rehash_conses(|heap, raw| fwd(heap, raw.into()).raw);
+    }
+
+    // cut g1 back and unbox the pages above
+    (self.page_ptr, self.g1_ptr) = (kept / (PAGESIZE - 1), kept % (PAGESIZE - 1) + 1);
+    let mut reclaimed = 0;
+    for page in self.page_ptr..G1PAGES {
+      if (page > self.page_ptr || self.g1_ptr == 1) && self.unbox_page(page) {
+        reclaimed += 1;
+      }
+    }
+    marks.fill(0);
+    self.marks = marks;
+    #[cfg(feature = "checked-idx")]
+    next_epoch(&mut self.g1_epoch);
+    Ok(reclaimed)
+  }
+
+  /// the marking of `collect_g1`, from the roots below `base`, `extra` and
+  /// the references in `value`.
+  fn mark(
+    &mut self,
+    marks : &mut [u32],
+    base : Root,
+    extra : &[Idx<T>],
+    value : Option<&T>,
+  ) -> Result<(), HeapError> {
+    let mark = |marks : &mut [u32], roots : &mut Buf<(Idx<T>, U)>, r : Idx<T>| {
+      if r != 0.into() && !marked(marks, r.raw) {
+        set_mark(marks, r.raw);
+        roots.push((r, 0))?;
+      }
+      Ok(())
+    };
+    for i in 0..base.0 {
+      let root = self.roots[i].0;
+      mark(marks, &mut self.roots, root)?;
+    }
+    for &r in extra {
+      mark(marks, &mut self.roots, r)?;
+    }
+    // `visit_refs` cannot stop early, so the first failure is kept
+    let mut ret = Ok(());
+    if let Some(value) = value {
+      value.visit_refs(&mut |r| ret = ret.and_then(|()| mark(marks, &mut self.roots, r)));
+    }
+    while let Some((idx, _)) = self.pop_frame(base) {
+      ret?;
+      let obj = mem::take(self.slot_mut(idx));
+      obj.visit_refs(&mut |r| ret = ret.and_then(|()| mark(marks, &mut self.roots, r)));
+      *self.slot_mut(idx) = obj;
+    }
+    ret
+  }
+
+  /// do a slice of an incremental major collection, tracing or sweeping
+  /// about `budget` objects, and start one first if none is running. returns
+  /// whether the collection is done.
+  ///
+  /// the heap stays usable in between. marking traces from a snapshot of the
+  /// roots taken at the start, `set`, `get_mut` and `cons` make sure nothing
+  /// reachable then gets lost, and everything allocated since counts as
+  /// live. the unmarked g1 slots then go on a free list instead of being
+  /// compacted, so nothing moves, but no pages are given back either: that
+  /// takes `collect_g1`.
+  pub fn collect_step(&mut self, budget : usize) -> Result<bool, HeapError> {
+    match self.phase {
+      Phase::Idle => {
+        // an empty nursery, so all of g2 is younger than the snapshot
+        self.collect_g2(&mut [])?;
+        if self.marks.is_empty() {
+          self.marks = Buf::zeroed(((G1PAGES + 1) * PAGESIZE).div_ceil(32));
+        }
+        for i in 0..self.roots.len() {
+          let root = self.roots[i].0;
+          if let Err(e) = Self::shade(&mut self.marks, &mut self.gray, root) {
+            self.abandon_step();
+            return Err(e);
+          }
+        }
+        (self.phase, self.barrier) = (Phase::Mark, Some(Self::shade_refs as _));
+      }
+      Phase::Mark => {
+        for _ in 0..budget {
+          let Some(raw) = self.gray.pop() else {
+            break;
+          };
+          let mut ret = Ok(());
+          let obj = mem::take(self.slot_mut(raw.into()));
+          obj.visit_refs(&mut |r| {
+            ret = ret.and_then(|()| Self::shade(&mut self.marks, &mut self.gray, r))
+          });
+          *self.slot_mut(raw.into()) = obj;
+          if let Err(e) = ret {
+            self.abandon_step();
+            return Err(e);
+          }
+        }
+        if self.gray.is_empty() {
+          // the hash-consing table does not keep anything alive
+          if !self.conses.is_empty() {
+            self.rehash_conses(|heap, raw| {
+              if Self::address(raw.into()).0 == G1PAGES || marked(&heap.marks, raw) {
+                raw
+              } else {
+                0
+              }
+            });
+          }
+          // the sweep rebuilds the free list, with what was left on it
+          (self.free_head, self.free_len) = (0, 0);
+          self.phase = Phase::Sweep {
+            at : 0,
+            end : self.g1_pos(),
+          };
+          self.barrier = None;
+        }
+      }
+      Phase::Sweep { mut at, end } => {
+        let stop = end.min(at + budget);
+        while at < stop {
+          let idx = Self::g1_at(at);
+          if marked(&self.marks, idx.raw) {
+            self.marks[idx.raw as usize / 32] &= !(1 << (idx.raw % 32));
+          } else {
+            *self.slot_mut(idx) = T::moved(self.free_head.into());
+            self.free_head = idx.raw;
+            self.free_len += 1;
+          }
+          at += 1;
+        }
+        if at == end {
+          self.phase = Phase::Idle;
+          return Ok(true);
+        }
+        self.phase = Phase::Sweep { at, end };
+      }
+    }
+    Ok(false)
+  }
+
+  /// mark `r` gray unless it is marked already. g2 needs no marks, all of it
+  /// is younger than the cycle.
+  /// fails if a static gray stack is full, leaving `r` unmarked. the cycle
+  /// then has to be abandoned, see `abandon_step`.
+  fn shade(marks : &mut [u32], gray : &mut Buf<U>, r : Idx<T>) -> Result<(), HeapError> {
+    if r != 0.into() && Self::address(r).0 != G1PAGES && !marked(marks, r.raw) {
+      gray.push(r.raw)?;
+      set_mark(marks, r.raw);
+    }
+    Ok(())
+  }
+
+  /// write barrier for `collect_step`: while marking, whatever the object at
+  /// `idx` points to gets shaded before it is overwritten, so it cannot drop
+  /// out of the snapshot.
+  fn shade_refs(&mut self, idx : Idx<T>) -> Result<(), HeapError> {
+    let mut ret = Ok(());
+    if self.phase == Phase::Mark {
+      let obj = mem::take(self.slot_mut(idx));
+      obj.visit_refs(&mut |r| {
+        ret = ret.and_then(|()| Self::shade(&mut self.marks, &mut self.gray, r))
+      });
+      *self.slot_mut(idx) = obj;
+    }
+    if ret.is_err() {
+      self.abandon_step();
+    }
+    ret
+  }
+
+  /// drop the cycle `collect_step` is in, as if it never started. a
+  /// snapshot some of whose objects could not be shaded would lose them.
+  fn abandon_step(&mut self) {
+    self.marks.fill(0);
+    self.gray.clear();
+    (self.phase, self.barrier) = (Phase::Idle, None);
+  }
+
+  /// promote whatever the g1 object at `page`, `offset` points to in g2.
+  fn forward_slot(&mut self, page : usize, offset : usize) {
+    let mut obj = mem::take(self.g1_slot(page, offset));
+    obj.map_refs(&mut |r| if r == 0.into() { r } else { self.forward(r) });
+    *self.g1_slot(page, offset) = obj;
+  }
+
+  /// allocate `init` in g2, collecting first if g2 is full. references
+  /// inside `init` are kept alive and updated by that collection, anything
+  /// else the caller still needs has to be rooted.
+  pub fn init(&mut self, mut init : T) -> Result<Idx<T>, HeapError> {
+    if self.g2_full() {
+      if self.g1_free() < self.g2_ptr - 1 {
+        self.collect_g1_(&mut [], Some(&mut init))?;
+      }
+      self.collect_g2_keeping(&mut init)?;
+    }
+    let new = self.alloc_g2()?;
+    *self.get_mut(new)? = init;
+    Ok(new)
+  }
+
+  /// overwrite the object at `idx`. if that puts references into g2 in a g1
+  /// object, the slot is remembered for the next minor collection.
+  pub fn set(&mut self, idx : Idx<T>, value : T) -> Result<(), HeapError> {
+    self.locate(idx)?;
+    self.shade_refs(idx)?;
+    if Self::address(idx).0 != G1PAGES {
+      let mut young = false;
+      value.visit_refs(&mut |r| young |= r != 0.into() && Self::address(r).0 == G1PAGES);
+      if young {
+        self.remember(idx);
+      }
+    }
+    *self.get_mut_(idx)? = value;
+    Ok(())
+  }
+
+  /// `collect_g2` with the references in `value` as extra roots.
+  fn collect_g2_keeping(&mut self, value : &mut T) -> Result<(), HeapError> {
+    self.collect_g2_(&mut [], Some(value))
+  }
+
+  /// hash-consing `init`: if an object equal to `value` was consed before and
+  /// is still around, that one is returned instead of allocating a new one.
+  /// consed objects must not be changed in place afterwards, or equal
+  /// contents stop meaning equal indices.
+  pub fn cons(&mut self, value : T) -> Result<Idx<T>, HeapError> {
+    if let Some(idx) = self.find_cons(&value) {
+      // it may have been garbage until now
+      if self.phase == Phase::Mark {
+        if let Err(e) = Self::shade(&mut self.marks, &mut self.gray, idx) {
+          self.abandon_step();
+          return Err(e);
+        }
+      }
+      return Ok(self.stamp(idx));
+    }
+    let idx = self.init(value)?;
+    if 2 * (self.consed + 1) > self.conses.len() {
+      let size = (2 * self.conses.len()).max(16);
+      if !self.conses_spare.resize(size, 0) {
+        return Err(HeapError::OutOfMemory);
+      }
+      self.rehash_conses(|_, raw| raw);
+      self.conses_spare.resize(size, 0);
+    }
+    self.insert_cons(idx);
+    Ok(idx)
+  }
+
+  fn cons_slot(&self, value : &T) -> usize {
+    let mut hasher = Fnv::default();
+    value.hash(&mut hasher);
+    hasher.finish() as usize & (self.conses.len() - 1)
+  }
+
+  fn find_cons(&self, value : &T) -> Option<Idx<T>> {
+    if self.conses.is_empty() {
+      return None;
+    }
+    let mut slot = self.cons_slot(value);
+    while self.conses[slot] != 0 {
+      let idx = self.conses[slot].into();
+      if self.get(idx).ok() == Some(value) {
+        return Some(idx);
+      }
+      slot = (slot + 1) & (self.conses.len() - 1);
+    }
+    None
+  }
+
+  fn insert_cons(&mut self, idx : Idx<T>) {
+    let mut slot = self.cons_slot(self.get(idx).expect("consed object"));
+    while self.conses[slot] != 0 {
+      slot = (slot + 1) & (self.conses.len() - 1);
+    }
+    self.conses[slot] = idx.raw;
+    self.consed += 1;
+  }
+
+  /// rebuild `conses` into `conses_spare` and swap the two, with every entry
+  /// renamed by `moved`, or dropped where that returns 0.
+  fn rehash_conses(&mut self, moved : impl Fn(&Self, U) -> U) {
+    let old = mem::replace(&mut self.conses, mem::take(&mut self.conses_spare));
+    self.conses.fill(0);
+    self.consed = 0;
+    for &raw in old.iter() {
+      if raw != 0 {
+        let raw = moved(self, raw);
+        if raw != 0 {
+          self.insert_cons(raw.into());
+        }
+      }
+    }
+    self.conses_spare = old;
+  }
+
+  /// check that every index reachable from the roots is non-null and points
+  /// at an allocated slot of a live page. returns how many objects are
+  /// reachable.
+  #[cfg(feature = "alloc")]
+  pub fn verify(&self) -> Result<usize, Corruption> {
+    let mut seen = vec![0u32; ((G1PAGES + 1) * PAGESIZE).div_ceil(32)];
+    let mut todo : Vec<(U, Idx<T>)> = Vec::new();
+    for &(root, _) in self.roots.iter() {
+      if root != 0.into() {
+        todo.push((0, root));
+      }
+    }
+    let mut reachable = 0;
+    while let Some((holder, idx)) = todo.pop() {
+      let corrupt = |error| Corruption { holder, error };
+      let (page, _) = self.locate(idx).map_err(corrupt)?;
+      if page != G1PAGES && self.g1_pages[page].is_none() {
+        return Err(corrupt(HeapError::Dangling(idx.raw)));
+      }
+      let (word, bit) = (idx.raw as usize / 32, 1 << (idx.raw % 32));
+      if seen[word] & bit != 0 {
+        continue;
+      }
+      seen[word] |= bit;
+      reachable += 1;
+      self
+        .get(idx)
+        .map_err(corrupt)?
+        .visit_refs(&mut |r| todo.push((idx.raw, r)));
+    }
+    Ok(reachable)
   }
 
-  pub fn init_with<F>(&mut self, init : F) -> Option<Idx<T>>
+  pub fn init_with<F>(&mut self, init : F) -> Result<Idx<T>, HeapError>
   where
     F : FnOnce() -> T,
   {
-    let new = self.alloc_g2();
-    *self.get_mut(new) = init();
-    Some(new)
+    self.init(init())
   }
 }
 
+#[cfg(feature = "alloc")]
 impl<T : Default, const PAGESIZE: usize, const G1PAGES: usize> Default
   for Heap<T, PAGESIZE, G1PAGES>
 {
@@ -129,7 +1292,17 @@ impl<T : Default, const PAGESIZE: usize, const G1PAGES: usize> Default
 
 pub trait Object
 where
-  Self : Sized,
+  Self : Sized + Eq + Hash,
 {
-  fn get_refs(&self) -> Box<dyn Iterator<Item = Idx<Self>>>;
+  /// call `f` on every reference, without allocating: the collector uses
+  /// this and has to keep working when the system heap is exhausted.
+  fn visit_refs(&self, f : &mut dyn FnMut(Idx<Self>));
+  /// rewrite every reference in place, used by the collector when objects
+  /// move.
+  fn map_refs(&mut self, f : &mut dyn FnMut(Idx<Self>) -> Idx<Self>);
+  /// what a major collection leaves behind in the slot of an object it moved
+  /// to `to`, or in a free slot with `to` the next one. it is only ever read
+  /// back by `moved_to`, so any encoding will do.
+  fn moved(to : Idx<Self>) -> Self;
+  fn moved_to(&self) -> Idx<Self>;
 }
@@ -1,7 +1,8 @@
 pub use crate::heap::U;
-use crate::heap::{Heap, Idx, Object};
+use crate::heap::{Heap, HeapError, Idx, Object, Root};
+use core::ops::ControlFlow;
 
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct Term(U, U);
 
 #[derive(Debug, Default)]
@@ -11,6 +12,9 @@ pub enum TermRepr {
   Var(U),
   Lam(Idx<Term>),
   App(Idx<Term>, Idx<Term>),
+  /// stands for the term it points to. `beta` leaves these behind where a
+  /// redex reduced to something shared, every traversal looks through them.
+  Ind(Idx<Term>),
 }
 
 impl From<TermRepr> for Term {
@@ -20,6 +24,8 @@ impl From<TermRepr> for Term {
       TermRepr::Var(u) => Term(0, u + 1),
       TermRepr::Lam(e) => Term(e.raw, 0),
       TermRepr::App(l, r) => Term(l.raw, r.raw),
+      // never an index, see `U`
+      TermRepr::Ind(to) => Term(to.raw, U::MAX),
     }
   }
 }
@@ -30,19 +36,34 @@ impl From<Term> for TermRepr {
       Term(0, 0) => TermRepr::Hole,
       Term(0, u) => TermRepr::Var(u - 1),
       Term(l, 0) => TermRepr::Lam(l.into()),
+      Term(to, U::MAX) => TermRepr::Ind(to.into()),
       Term(l, r) => TermRepr::App(l.into(), r.into()),
     }
   }
 }
 
 impl Object for Term {
-  fn get_refs(&self) -> Box<dyn Iterator<Item = Idx<Self>>> {
+  fn visit_refs(&self, f : &mut dyn FnMut(Idx<Self>)) {
     match TermRepr::from(*self) {
-      TermRepr::Hole | TermRepr::Var(_) => Box::new(core::iter::empty()),
-      TermRepr::Lam(e) => Box::new(core::iter::once(e)),
-      TermRepr::App(l, r) => Box::new(vec![l, r].into_iter()),
+      TermRepr::Hole | TermRepr::Var(_) => {}
+      TermRepr::Lam(e) | TermRepr::Ind(e) => f(e),
+      TermRepr::App(l, r) => {
+        f(l);
+        f(r);
+      }
+    }
+  }
+  fn map_refs(&mut self, f : &mut dyn FnMut(Idx<Self>) -> Idx<Self>) {
+    *self = match TermRepr::from(*self) {
+      TermRepr::Lam(e) => TermRepr::Lam(f(e)),
+      TermRepr::App(l, r) => TermRepr::App(f(l), f(r)),
+      TermRepr::Ind(to) => TermRepr::Ind(f(to)),
+      t => t,
     }
+    .into();
   }
+  fn moved(to : Idx<Self>) -> Self { Term(to.raw, 0) }
+  fn moved_to(&self) -> Idx<Self> { self.0.into() }
 }
 
 impl core::fmt::Debug for Term {
@@ -51,133 +72,489 @@ impl core::fmt::Debug for Term {
   }
 }
 
+// tags of the frames the traversals below keep on the root stack, instead of
+// recursing once per node
+/// the frame's node is still to be visited
+const VISIT : U = 0;
+/// walked back out of a binder
+const LEAVE : U = 1;
+/// the body of the frame's `Lam` is done
+const LAM : U = 2;
+/// the left side of the frame's `App` is done
+const APP_L : U = 3;
+/// the frame holds the new left side, the right one is in progress
+const APP_R : U = 4;
+/// `APP_R`, and the left side differs from the original
+const APP_R_CHANGED : U = 5;
+/// the original `App`, always right below its `APP_R`
+const APP : U = 6;
+
 impl<const PAGESIZE: usize, const G1PAGES: usize> Heap<Term, PAGESIZE, G1PAGES> {
-  pub fn duplicate(&mut self, at : Idx<Term>) -> Option<Idx<Term>> {
-    match TermRepr::from(*self.get(at)) {
-      TermRepr::Hole => self.init(TermRepr::Hole.into()),
-      TermRepr::Var(u) => self.init(TermRepr::Var(u).into()),
-      TermRepr::Lam(e) => {
-        let new_e = self.duplicate(e)?;
-        self.init(TermRepr::Lam(new_e).into())
-      }
-      TermRepr::App(l, r) => {
-        let new_l = self.duplicate(l)?;
-        let new_r = self.duplicate(r)?;
-        self.init(TermRepr::App(new_l, new_r).into())
+  /// deep copy of the term at `at`. collections while copying are fine, the
+  /// parts still to be copied are rooted along the way.
+  pub fn duplicate(&mut self, at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.duplicate_(base, at, Self::init);
+    self.unroot(base);
+    ret
+  }
+  /// hash-consed copy of `at`, see `Heap::cons`. equal terms interned this
+  /// way get equal indices.
+  pub fn intern(&mut self, at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.duplicate_(base, at, Self::cons);
+    self.unroot(base);
+    ret
+  }
+  /// `build` allocates each node of the copy
+  fn duplicate_(
+    &mut self,
+    base : Root,
+    at : Idx<Term>,
+    build : fn(&mut Self, Term) -> Result<Idx<Term>, HeapError>,
+  ) -> Result<Idx<Term>, HeapError> {
+    // the copy of the subterm finished last
+    let mut ret = at;
+    self.push_frame(at, VISIT)?;
+    while let Some((idx, tag)) = self.pop_frame(base) {
+      match tag {
+        VISIT => match TermRepr::from(*self.get(idx)?) {
+          TermRepr::Lam(e) => {
+            self.push_frame(0.into(), LAM)?;
+            self.push_frame(e, VISIT)?;
+          }
+          TermRepr::App(l, r) => {
+            self.push_frame(r, APP_L)?;
+            self.push_frame(l, VISIT)?;
+          }
+          TermRepr::Ind(to) => {
+            self.push_frame(to, VISIT)?;
+          }
+          leaf => ret = build(self, leaf.into())?,
+        },
+        LAM => ret = build(self, TermRepr::Lam(ret).into())?,
+        APP_L => {
+          self.push_frame(ret, APP_R)?;
+          self.push_frame(idx, VISIT)?;
+        }
+        _ => ret = build(self, TermRepr::App(idx, ret).into())?,
       }
     }
+    Ok(ret)
   }
+
   pub fn duplicate_from<const OPS: usize, const OG1S: usize>(
     &mut self,
     other : &Heap<Term, OPS, OG1S>,
     at : Idx<Term>,
-  ) -> Option<Idx<Term>> {
-    match TermRepr::from(*other.get(at)) {
-      TermRepr::Hole => self.init(TermRepr::Hole.into()),
-      TermRepr::Var(u) => self.init(TermRepr::Var(u).into()),
-      TermRepr::Lam(e) => {
-        let new_e = self.duplicate_from(other, e)?;
-        self.init(TermRepr::Lam(new_e).into())
-      }
-      TermRepr::App(l, r) => {
-        let new_l = self.duplicate_from(other, l)?;
-        let new_r = self.duplicate_from(other, r)?;
-        self.init(TermRepr::App(new_l, new_r).into())
+  ) -> Result<Idx<Term>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.duplicate_from_(base, other, at, Self::init);
+    self.unroot(base);
+    ret
+  }
+  /// `intern`, from another heap
+  pub fn intern_from<const OPS: usize, const OG1S: usize>(
+    &mut self,
+    other : &Heap<Term, OPS, OG1S>,
+    at : Idx<Term>,
+  ) -> Result<Idx<Term>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.duplicate_from_(base, other, at, Self::cons);
+    self.unroot(base);
+    ret
+  }
+  fn duplicate_from_<const OPS: usize, const OG1S: usize>(
+    &mut self,
+    base : Root,
+    other : &Heap<Term, OPS, OG1S>,
+    at : Idx<Term>,
+    build : fn(&mut Self, Term) -> Result<Idx<Term>, HeapError>,
+  ) -> Result<Idx<Term>, HeapError> {
+    // same as `duplicate_`, but indices into `other` must not be seen by our
+    // collector: they go in the tag of a null frame right below the frame
+    // that needs them.
+    let mut ret = at;
+    self.push_frame(0.into(), at.raw)?;
+    self.push_frame(0.into(), VISIT)?;
+    while let Some((idx, tag)) = self.pop_frame(base) {
+      match tag {
+        VISIT | APP_L => {
+          let (_, src) = self.pop_frame(base).expect("foreign index frame");
+          if tag == APP_L {
+            self.push_frame(ret, APP_R)?;
+          }
+          match TermRepr::from(*other.get(src.into())?) {
+            TermRepr::Lam(e) => {
+              self.push_frame(0.into(), LAM)?;
+              self.push_frame(0.into(), e.raw)?;
+              self.push_frame(0.into(), VISIT)?;
+            }
+            TermRepr::App(l, r) => {
+              self.push_frame(0.into(), r.raw)?;
+              self.push_frame(0.into(), APP_L)?;
+              self.push_frame(0.into(), l.raw)?;
+              self.push_frame(0.into(), VISIT)?;
+            }
+            TermRepr::Ind(to) => {
+              self.push_frame(0.into(), to.raw)?;
+              self.push_frame(0.into(), VISIT)?;
+            }
+            leaf => ret = build(self, leaf.into())?,
+          }
+        }
+        LAM => ret = build(self, TermRepr::Lam(ret).into())?,
+        _ => ret = build(self, TermRepr::App(idx, ret).into())?,
       }
     }
+    Ok(ret)
   }
 
-  #[must_use]
-  pub fn is_redux(&self, at : Idx<Term>) -> bool {
-    if let TermRepr::App(l, _) = TermRepr::from(*self.get(at)) {
-      if let TermRepr::Lam(_) = TermRepr::from(*self.get(l)) {
-        return true;
-      }
+  /// where `at` ends up after following indirections.
+  pub fn follow(&self, mut at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    while let TermRepr::Ind(to) = TermRepr::from(*self.get(at)?) {
+      at = to;
     }
-    false
+    Ok(at)
   }
 
-  #[must_use]
-  pub fn head(&self, at : Idx<Term>) -> Option<Idx<Term>> {
-    if self.is_redux(at) {
-      Some(at)
-    } else {
-      match TermRepr::from(*self.get(at)) {
-        TermRepr::Var(_) | TermRepr::Hole => None,
-        TermRepr::Lam(e) => self.head(e),
-        TermRepr::App(l, _) => self.head(l),
+  pub fn is_redux(&self, at : Idx<Term>) -> Result<bool, HeapError> {
+    if let TermRepr::App(l, _) = TermRepr::from(*self.get(at)?) {
+      if let TermRepr::Lam(_) = TermRepr::from(*self.get(self.follow(l)?)?) {
+        return Ok(true);
       }
     }
+    Ok(false)
   }
 
-  #[must_use]
-  pub fn redux(&self, at : Idx<Term>) -> Option<Idx<Term>> {
-    if self.is_redux(at) {
-      Some(at)
-    } else {
-      match TermRepr::from(*self.get(at)) {
-        TermRepr::Var(_) | TermRepr::Hole => None,
-        TermRepr::Lam(e) => self.redux(e),
-        TermRepr::App(l, r) => self.redux(l).or_else(|| self.redux(r)),
+  pub fn head(&self, mut at : Idx<Term>) -> Result<Option<Idx<Term>>, HeapError> {
+    loop {
+      if self.is_redux(at)? {
+        return Ok(Some(at));
+      }
+      match TermRepr::from(*self.get(at)?) {
+        TermRepr::Var(_) | TermRepr::Hole => return Ok(None),
+        TermRepr::Lam(e) | TermRepr::Ind(e) => at = e,
+        TermRepr::App(l, _) => at = l,
       }
     }
   }
 
-  pub fn shift(&mut self, at : Idx<Term>, level : U, amount : U) {
-    match TermRepr::from(*self.get(at)) {
-      TermRepr::Hole => {}
-      TermRepr::Var(u) => {
+  pub fn redux(&mut self, at : Idx<Term>) -> Result<Option<Idx<Term>>, HeapError> {
+    self.walk(at, 0, |heap, at, _| {
+      Ok(if heap.is_redux(at)? {
+        ControlFlow::Break(at)
+      } else {
+        ControlFlow::Continue(())
+      })
+    })
+  }
+
+  pub fn shift(&mut self, at : Idx<Term>, level : U, amount : U) -> Result<(), HeapError> {
+    self.walk(at, level, |heap, at, level| {
+      if let TermRepr::Var(u) = TermRepr::from(*heap.get(at)?) {
         if u >= level {
-          *self.get_mut(at) = TermRepr::Var(u + amount).into();
+          *heap.get_mut(at)? = TermRepr::Var(u + amount).into();
         }
       }
-      TermRepr::Lam(e) => self.shift(e, level + 1, amount),
-      TermRepr::App(l, r) => {
-        self.shift(l, level, amount);
-        self.shift(r, level, amount);
-      }
-    }
+      Ok(ControlFlow::<()>::Continue(()))
+    })?;
+    Ok(())
   }
 
-  pub fn replace_closed(&mut self, at : Idx<Term>, var : U, with : Idx<Term>) -> Option<Idx<Term>> {
-    match TermRepr::from(*self.get(at)) {
-      TermRepr::Hole => Some(at),
-      TermRepr::Var(v) => {
-        if v == var {
-          Some(with)
-        } else {
-          Some(at)
-        }
+  /// `closed(at, 0)` to check if term is closed
+  pub fn closed(&mut self, at : Idx<Term>, level : U) -> Result<bool, HeapError> {
+    let free = self.walk(at, level, |heap, at, level| {
+      Ok(match TermRepr::from(*heap.get(at)?) {
+        TermRepr::Var(u) if u >= level => ControlFlow::Break(()),
+        _ => ControlFlow::Continue(()),
+      })
+    })?;
+    Ok(free.is_none())
+  }
+
+  /// pre-order walk over `at`, left before right, handing `f` every node with
+  /// the number of binders above it (starting from `level`). stops at the
+  /// first `Break`. `f` must not allocate.
+  fn walk<B>(
+    &mut self,
+    at : Idx<Term>,
+    level : U,
+    mut f : impl FnMut(&mut Self, Idx<Term>, U) -> Result<ControlFlow<B>, HeapError>,
+  ) -> Result<Option<B>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.walk_(base, at, level, &mut f);
+    self.unroot(base);
+    ret
+  }
+  fn walk_<B>(
+    &mut self,
+    base : Root,
+    at : Idx<Term>,
+    mut level : U,
+    f : &mut impl FnMut(&mut Self, Idx<Term>, U) -> Result<ControlFlow<B>, HeapError>,
+  ) -> Result<Option<B>, HeapError> {
+    self.push_frame(at, VISIT)?;
+    while let Some((idx, tag)) = self.pop_frame(base) {
+      if tag == LEAVE {
+        level -= 1;
+        continue;
       }
-      TermRepr::Lam(e) => {
-        let new_e = self.replace_closed(e, var + 1, with)?;
-        if new_e == e {
-          Some(at)
-        } else {
-          self.init(TermRepr::Lam(new_e).into())
-        }
+      if let ControlFlow::Break(b) = f(self, idx, level)? {
+        return Ok(Some(b));
       }
-      TermRepr::App(l, r) => {
-        let new_l = self.replace_closed(l, var, with)?;
-        let new_r = self.replace_closed(r, var, with)?;
-        if new_l == l && new_r == r {
-          Some(at)
-        } else {
-          self.init(TermRepr::App(new_l, new_r).into())
+      match TermRepr::from(*self.get(idx)?) {
+        TermRepr::Lam(e) => {
+          level += 1;
+          self.push_frame(0.into(), LEAVE)?;
+          self.push_frame(e, VISIT)?;
+        }
+        TermRepr::App(l, r) => {
+          self.push_frame(r, VISIT)?;
+          self.push_frame(l, VISIT)?;
+        }
+        TermRepr::Ind(to) => {
+          self.push_frame(to, VISIT)?;
         }
+        TermRepr::Var(_) | TermRepr::Hole => {}
       }
     }
+    Ok(None)
+  }
+
+  /// substitute the closed term `with` for `var` in `at`. only the spine
+  /// down to the replaced occurrences is copied, everything else (including
+  /// `with`) is shared.
+  pub fn replace_closed(
+    &mut self,
+    at : Idx<Term>,
+    var : U,
+    with : Idx<Term>,
+  ) -> Result<Idx<Term>, HeapError> {
+    let mark = self.root_mark();
+    let with = self.root(with)?;
+    let ret = self.rebuild(at, var, |heap, v, var, _| {
+      Ok((v == var).then(|| heap.rooted(with)))
+    });
+    self.unroot(mark);
+    ret
   }
 
+  /// `at[var := with]` the way a beta contraction needs it: occurrences of
+  /// `var` become `with` shifted over the `level` binders crossed on the way
+  /// down (copied, unless nothing needs shifting), and variables above `var`
+  /// lose the binder being contracted so they move down by one.
   pub fn replace(
     &mut self,
     at : Idx<Term>,
     var : U,
     with : Idx<Term>,
     level : U,
-  ) -> Option<Idx<Term>> {
-    todo!()
+  ) -> Result<Idx<Term>, HeapError> {
+    let mark = self.root_mark();
+    let with_closed = self.closed(with, 0)?;
+    let with = self.root(with)?;
+    let ret = self.rebuild(at, var, |heap, v, var, depth| {
+      if v == var {
+        if with_closed || level + depth == 0 {
+          Ok(Some(heap.rooted(with)))
+        } else {
+          let copy = heap.duplicate(heap.rooted(with))?;
+          heap.shift(copy, 0, level + depth)?;
+          Ok(Some(copy))
+        }
+      } else if v > var {
+        Ok(Some(heap.init(TermRepr::Var(v - 1).into())?))
+      } else {
+        Ok(None)
+      }
+    });
+    self.unroot(mark);
+    ret
   }
 
-  pub fn beta(&mut self, at : Idx<Term>) {}
+  /// rebuild `at` with variables replaced by whatever `on_var` returns for
+  /// them, if anything. `on_var` gets the variable, what `var` is at that
+  /// depth and the number of binders crossed. subterms where nothing changed
+  /// are shared, not copied.
+  fn rebuild(
+    &mut self,
+    at : Idx<Term>,
+    var : U,
+    mut on_var : impl FnMut(&mut Self, U, U, U) -> Result<Option<Idx<Term>>, HeapError>,
+  ) -> Result<Idx<Term>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.rebuild_(base, at, var, &mut on_var);
+    self.unroot(base);
+    ret
+  }
+  fn rebuild_(
+    &mut self,
+    base : Root,
+    at : Idx<Term>,
+    var : U,
+    on_var : &mut impl FnMut(&mut Self, U, U, U) -> Result<Option<Idx<Term>>, HeapError>,
+  ) -> Result<Idx<Term>, HeapError> {
+    let mut depth = 0;
+    // the subterm finished last, and whether it is new
+    let (mut ret, mut changed) = (at, false);
+    self.push_frame(at, VISIT)?;
+    while let Some((idx, tag)) = self.pop_frame(base) {
+      match tag {
+        VISIT => match TermRepr::from(*self.get(idx)?) {
+          TermRepr::Var(v) => match on_var(self, v, var + depth, depth)? {
+            Some(new) => (ret, changed) = (new, true),
+            None => (ret, changed) = (idx, false),
+          },
+          TermRepr::Hole => (ret, changed) = (idx, false),
+          TermRepr::Lam(e) => {
+            depth += 1;
+            self.push_frame(idx, LAM)?;
+            self.push_frame(e, VISIT)?;
+          }
+          TermRepr::App(l, _) => {
+            self.push_frame(idx, APP_L)?;
+            self.push_frame(l, VISIT)?;
+          }
+          // the result stands in for the indirection as well
+          TermRepr::Ind(to) => {
+            self.push_frame(to, VISIT)?;
+          }
+        },
+        LAM => {
+          depth -= 1;
+          if changed {
+            ret = self.init(TermRepr::Lam(ret).into())?;
+          } else {
+            ret = idx;
+          }
+        }
+        APP_L => {
+          let TermRepr::App(_, r) = TermRepr::from(*self.get(idx)?) else {
+            unreachable!()
+          };
+          self.push_frame(idx, APP)?;
+          self.push_frame(ret, if changed { APP_R_CHANGED } else { APP_R })?;
+          self.push_frame(r, VISIT)?;
+        }
+        _ => {
+          let (app, _) = self.pop_frame(base).expect("APP frame");
+          if changed || tag == APP_R_CHANGED {
+            ret = self.init(TermRepr::App(idx, ret).into())?;
+            changed = true;
+          } else {
+            ret = app;
+          }
+        }
+      }
+    }
+    Ok(ret)
+  }
+
+  /// contract the redex at `at` in place, so everything pointing at it sees
+  /// the result. returns `false` (and does nothing) if it is not a redex.
+  ///
+  /// if the result is a shared application, the argument or a body that
+  /// never uses it, `at` becomes an `Ind` to it rather than a copy of its top
+  /// node: a copy of an unreduced redex would get reduced once for every
+  /// place using it.
+  pub fn beta(&mut self, at : Idx<Term>) -> Result<bool, HeapError> {
+    let TermRepr::App(l, r) = TermRepr::from(*self.get(at)?) else {
+      return Ok(false);
+    };
+    let TermRepr::Lam(body) = TermRepr::from(*self.get(self.follow(l)?)?) else {
+      return Ok(false);
+    };
+    // nothing to shift either way
+    let closed = self.closed(r, 0)? && self.closed(body, 1)?;
+    let mark = self.root_mark();
+    let (ra, rr, rb) = (self.root(at)?, self.root(r)?, self.root(body)?);
+    let result = if closed {
+      self.replace_closed(body, 0, r)
+    } else {
+      self.replace(body, 0, r, 0)
+    };
+    let (at, r, body) = (self.rooted(ra), self.rooted(rr), self.rooted(rb));
+    self.unroot(mark);
+    let result = result?;
+    let result = self.follow(result)?;
+    let update = *self.get(result)?;
+    let update = match TermRepr::from(update) {
+      TermRepr::App(..) if result == r || result == body => TermRepr::Ind(result).into(),
+      _ => update,
+    };
+    self.set(at, update)?;
+    Ok(true)
+  }
+
+  /// reduce to weak head normal form by graph reduction: unwind the spine of
+  /// applications to its head, and contract there while it is a lambda with
+  /// an argument. nothing under a binder or in an argument is touched, and
+  /// as `beta` works in place, an argument used in several places gets
+  /// reduced at most once for all of them. returns where `at` lives
+  /// afterwards, with indirections followed. loops forever if there is no
+  /// weak head normal form.
+  pub fn whnf(&mut self, at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    let base = self.root_mark();
+    let ret = self.whnf_(at);
+    self.unroot(base);
+    ret
+  }
+  fn whnf_(&mut self, at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    let top = self.root(at)?;
+    let head = self.root(at)?;
+    // the applications from `top` down to `head`, innermost on top
+    let spine = self.root_mark();
+    loop {
+      let at = self.follow(self.rooted(head))?;
+      match TermRepr::from(*self.get(at)?) {
+        TermRepr::App(l, _) => {
+          self.push_frame(at, APP)?;
+          self.set_root(head, l);
+        }
+        TermRepr::Lam(_) => {
+          let Some((app, _)) = self.pop_frame(spine) else {
+            break;
+          };
+          self.beta(app)?;
+          self.set_root(head, app);
+        }
+        _ => break,
+      }
+    }
+    self.follow(self.rooted(top))
+  }
+
+  /// reduce to head normal form, contracting `head` redexes. returns where
+  /// `at` lives afterwards, collections along the way can move it.
+  pub fn hnf(&mut self, at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    self.drive(at, |heap, at| heap.head(at))
+  }
+
+  /// reduce to normal form, contracting leftmost outermost (`redux`) redexes.
+  /// loops forever if there is none. returns where `at` lives afterwards.
+  pub fn nf(&mut self, at : Idx<Term>) -> Result<Idx<Term>, HeapError> {
+    self.drive(at, Self::redux)
+  }
+
+  fn drive(
+    &mut self,
+    at : Idx<Term>,
+    find : impl Fn(&mut Self, Idx<Term>) -> Result<Option<Idx<Term>>, HeapError>,
+  ) -> Result<Idx<Term>, HeapError> {
+    let ra = self.root(at)?;
+    let ret = loop {
+      match find(self, self.rooted(ra)) {
+        Ok(Some(redex)) => {
+          if let Err(e) = self.beta(redex) {
+            break Err(e);
+          }
+        }
+        Ok(None) => break Ok(self.rooted(ra)),
+        Err(e) => break Err(e),
+      }
+    };
+    self.unroot(ra);
+    ret
+  }
 }
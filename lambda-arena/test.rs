@@ -7,22 +7,23 @@
 fn test_heap() {
   use crate::heap::*;
   let mut heap : Heap<(u16, u16), 10, 0> = Heap::new();
-  assert_eq!(*heap.get(1.into()), (0u16, 0u16));
-  let new = heap.alloc_g2();
+  assert_eq!(heap.get(1.into()), Err(HeapError::Dangling(1)));
+  let new = heap.alloc_g2().unwrap();
   assert_eq!(new.raw, 1);
-  *heap.get_mut(new) = (1u16, 1u16);
-  assert_eq!(*heap.get(new), (1u16, 1u16));
+  *heap.get_mut(new).unwrap() = (1u16, 1u16);
+  assert_eq!(*heap.get(new).unwrap(), (1u16, 1u16));
 }
 
 #[test]
 fn test_lambda_arena_dup_from() {
   use crate::heap::*;
   use crate::lambda::*;
-  let mut heap: Heap<Term, 20, 0> = Heap::new();
+  let mut heap : Heap<Term, 20, 0> = Heap::new();
   let dzero = heap.init_with(|| TermRepr::Var(0).into()).unwrap();
   let id = heap.init_with(|| TermRepr::Lam(dzero).into()).unwrap();
   let id2 = heap.duplicate(id).unwrap();
   let idid = heap.init_with(|| TermRepr::App(id, id2).into()).unwrap();
+  assert_eq!(show(&heap, idid), "(λ0 λ0)");
 }
 
 // #[test]
@@ -38,3 +39,1036 @@ fn test_lambda_arena_dup_from() {
 //   let zero = arena.replace_closed(free_occur, 0, id).unwrap();
 //   dbg!(a);
 // }
+
+#[test]
+fn test_collect_g2() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 8, 4> = Heap::new();
+  let dzero = heap.init(TermRepr::Var(0).into()).unwrap();
+  let id = heap.init(TermRepr::Lam(dzero).into()).unwrap();
+  let _garbage = heap.init(TermRepr::Var(3).into()).unwrap();
+  let idid = heap.init(TermRepr::App(id, id).into()).unwrap();
+  let mut roots = [idid];
+  heap.collect_g2(&mut roots).unwrap();
+  assert!(!heap.g2_full());
+  let [idid] = roots;
+  assert_eq!(idid.raw, 1);
+  let TermRepr::App(l, r) = TermRepr::from(*heap.get(idid).unwrap()) else {
+    panic!()
+  };
+  assert_eq!(l, r);
+  let TermRepr::Lam(e) = TermRepr::from(*heap.get(l).unwrap()) else {
+    panic!()
+  };
+  assert!(matches!(
+    TermRepr::from(*heap.get(e).unwrap()),
+    TermRepr::Var(0)
+  ));
+  // only the three live nodes got promoted
+  assert_eq!(heap.alloc_g2().unwrap().raw, 4 * 8 + 1);
+  for _ in 0..6 {
+    heap.alloc_g2().unwrap();
+  }
+  assert!(heap.g2_full());
+}
+
+#[test]
+fn test_collect_g2_spills_pages() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 4, 8> = Heap::new();
+  let mut root = heap.init(TermRepr::Var(0).into()).unwrap();
+  for _ in 0..20 {
+    if heap.g2_full() {
+      let mut roots = [root];
+      heap.collect_g2(&mut roots).unwrap();
+      root = roots[0];
+    }
+    root = heap.init(TermRepr::Lam(root).into()).unwrap();
+  }
+  let mut depth = 0;
+  while let TermRepr::Lam(e) = TermRepr::from(*heap.get(root).unwrap()) {
+    root = e;
+    depth += 1;
+  }
+  assert_eq!(depth, 20);
+}
+
+#[cfg(test)]
+fn show<const P: usize, const G: usize>(
+  heap : &crate::heap::Heap<crate::lambda::Term, P, G>,
+  at : crate::heap::Idx<crate::lambda::Term>,
+) -> String {
+  use crate::lambda::*;
+  match TermRepr::from(*heap.get(at).unwrap()) {
+    TermRepr::Hole => "_".into(),
+    TermRepr::Var(u) => format!("{u}"),
+    TermRepr::Lam(e) => format!("λ{}", show(heap, e)),
+    TermRepr::App(l, r) => format!("({} {})", show(heap, l), show(heap, r)),
+    TermRepr::Ind(to) => show(heap, to),
+  }
+}
+
+#[test]
+fn test_roots_survive_collection() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 4, 32> = Heap::new();
+  let x = heap.init(TermRepr::Var(1).into()).unwrap();
+  let y = heap.init(TermRepr::Var(2).into()).unwrap();
+  let rx = heap.root(x).unwrap();
+  let ry = heap.root(y).unwrap();
+  for _ in 0..10 {
+    heap.init(TermRepr::Hole.into()).unwrap();
+  }
+  assert_ne!(heap.rooted(rx), x);
+  assert_eq!(show(&heap, heap.rooted(rx)), "1");
+  assert_eq!(show(&heap, heap.rooted(ry)), "2");
+  heap.unroot(ry);
+  assert_eq!(heap.root_mark(), ry);
+  heap.unroot(rx);
+}
+
+#[test]
+fn test_duplicate_across_collections() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 4, 64> = Heap::new();
+  let x = heap.init(TermRepr::Var(0).into()).unwrap();
+  let y = heap.init(TermRepr::Var(1).into()).unwrap();
+  let xy = heap.init(TermRepr::App(x, y).into()).unwrap();
+  let mut term = heap.init(TermRepr::Lam(xy).into()).unwrap();
+  let mark = heap.root_mark();
+  for _ in 0..3 {
+    let r = heap.root(term).unwrap();
+    let copy = heap.duplicate(term).unwrap();
+    term = heap.rooted(r);
+    heap.unroot(r);
+    term = heap.init(TermRepr::App(term, copy).into()).unwrap();
+  }
+  let expected = "(((λ(0 1) λ(0 1)) (λ(0 1) λ(0 1))) ((λ(0 1) λ(0 1)) (λ(0 1) λ(0 1))))";
+  assert_eq!(show(&heap, term), expected);
+  assert_eq!(heap.root_mark(), mark);
+}
+
+#[test]
+fn test_replace_closed_across_collections() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut src : Heap<Term, 16, 0> = Heap::new();
+  let dzero = src.init(TermRepr::Var(0).into()).unwrap();
+  let id = src.init(TermRepr::Lam(dzero).into()).unwrap();
+  let v1 = src.init(TermRepr::Var(1).into()).unwrap();
+  let v0 = src.init(TermRepr::Var(0).into()).unwrap();
+  let body = src.init(TermRepr::App(v1, v0).into()).unwrap();
+  let body = src.init(TermRepr::App(body, v0).into()).unwrap();
+  let lam = src.init(TermRepr::Lam(body).into()).unwrap();
+
+  let mut heap : Heap<Term, 4, 64> = Heap::new();
+  let id = heap.duplicate_from(&src, id).unwrap();
+  let rid = heap.root(id).unwrap();
+  let lam = heap.duplicate_from(&src, lam).unwrap();
+  let id = heap.rooted(rid);
+  let replaced = heap.replace_closed(lam, 0, id).unwrap();
+  assert_eq!(show(&heap, replaced), "λ((λ0 0) 0)");
+  heap.unroot(rid);
+  assert_eq!(heap.root_mark(), rid);
+}
+
+#[test]
+fn test_out_of_memory() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 4, 2> = Heap::new();
+  assert_eq!(heap.get(0.into()), Err(HeapError::Null));
+  let mut term = heap.init(TermRepr::Var(0).into()).unwrap();
+  let err = loop {
+    match heap.init(TermRepr::Lam(term).into()) {
+      Ok(t) => term = t,
+      Err(e) => break e,
+    }
+  };
+  assert_eq!(err, HeapError::OutOfMemory);
+  assert_eq!(err.to_string(), "out of memory");
+  // the last good term is still intact and the heap still answers. rooted,
+  // or the major collection below would find it all garbage
+  heap.root(term).unwrap();
+  let mut depth = 0;
+  while let TermRepr::Lam(e) = TermRepr::from(*heap.get(term).unwrap()) {
+    term = e;
+    depth += 1;
+  }
+  assert!(depth > 0);
+  assert_eq!(heap.duplicate(term).unwrap_err(), HeapError::OutOfMemory);
+}
+
+/// inverse of `show`, builds in a scratch heap and copies over so `heap` is
+/// free to collect.
+#[cfg(test)]
+fn build<const P: usize, const G: usize>(
+  heap : &mut crate::heap::Heap<crate::lambda::Term, P, G>,
+  src : &str,
+) -> crate::heap::Idx<crate::lambda::Term> {
+  use crate::heap::*;
+  use crate::lambda::*;
+  fn parse(scratch : &mut Heap<Term, 4096, 0>, s : &mut core::str::Chars) -> Idx<Term> {
+    let s_ = s.as_str().trim_start();
+    *s = s_.chars();
+    let repr = match s.next().unwrap() {
+      '_' => TermRepr::Hole,
+      'λ' => TermRepr::Lam(parse(scratch, s)),
+      '(' => {
+        let l = parse(scratch, s);
+        let r = parse(scratch, s);
+        assert_eq!(s.next(), Some(')'));
+        TermRepr::App(l, r)
+      }
+      c => {
+        let mut n = c.to_digit(10).unwrap() as U;
+        while let Some(d) = s.clone().next().and_then(|c| c.to_digit(10)) {
+          s.next();
+          n = n * 10 + d as U;
+        }
+        TermRepr::Var(n)
+      }
+    };
+    scratch.init(repr.into()).unwrap()
+  }
+  let mut scratch : Box<Heap<Term, 4096, 0>> = Box::default();
+  let at = parse(&mut scratch, &mut src.chars());
+  heap.duplicate_from(&scratch, at).unwrap()
+}
+
+#[test]
+fn test_beta() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 64, 4> = Heap::new();
+  let id_x = build(&mut heap, "(λ0 7)");
+  assert!(heap.beta(id_x).unwrap());
+  assert_eq!(show(&heap, id_x), "7");
+  assert!(!heap.beta(id_x).unwrap());
+  // free variables of the body move down, the argument moves up under binders
+  let t = build(&mut heap, "(λλ(2 (1 0)) λ(0 4))");
+  assert!(heap.beta(t).unwrap());
+  assert_eq!(show(&heap, t), "λ(1 (λ(0 5) 0))");
+  // redex deeper in the term is rewritten in place
+  let t = build(&mut heap, "λ(λλ1 0)");
+  let TermRepr::Lam(redex) = TermRepr::from(*heap.get(t).unwrap()) else {
+    panic!()
+  };
+  assert!(heap.beta(redex).unwrap());
+  assert_eq!(show(&heap, t), "λλ1");
+}
+
+#[test]
+fn test_hnf() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 64, 4> = Heap::new();
+  let t = build(&mut heap, "λ((λλ1 0) (λ0 λ0))");
+  let t = heap.hnf(t).unwrap();
+  assert_eq!(show(&heap, t), "λ0");
+  // only the head gets reduced
+  let t = build(&mut heap, "λ(0 (λ0 1))");
+  let t = heap.hnf(t).unwrap();
+  assert_eq!(show(&heap, t), "λ(0 (λ0 1))");
+}
+
+#[test]
+fn test_nf_arithmetic() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let plus = "λλλλ((3 1) ((2 1) 0))";
+  let times = "λλλλ((3 (2 1)) 0)";
+  // small nursery, so this runs through plenty of collections
+  let mut heap : Heap<Term, 32, 64> = Heap::new();
+  let t = build(
+    &mut heap,
+    &format!("(({plus} {}) {})", church(2), church(3)),
+  );
+  let t = heap.nf(t).unwrap();
+  assert_eq!(show(&heap, t), church(5));
+  let t = build(
+    &mut heap,
+    &format!("(({times} {}) {})", church(2), church(3)),
+  );
+  let t = heap.nf(t).unwrap();
+  assert_eq!(show(&heap, t), church(6));
+  let power = "λλλλ(((2 3) 1) 0)";
+  let t = build(
+    &mut heap,
+    &format!("(({power} {}) {})", church(2), church(3)),
+  );
+  let t = heap.nf(t).unwrap();
+  assert_eq!(show(&heap, t), church(8));
+}
+
+/// the church numeral `n`, in the syntax `build` reads
+#[cfg(test)]
+fn church(n : usize) -> String {
+  let mut s = String::from("0");
+  for _ in 0..n {
+    s = format!("(1 {s})");
+  }
+  format!("λλ{s}")
+}
+
+/// church numeral `n`, built bottom up so the test itself does not recurse
+#[cfg(test)]
+fn numeral<const P: usize, const G: usize>(
+  heap : &mut crate::heap::Heap<crate::lambda::Term, P, G>,
+  n : usize,
+) -> crate::heap::Idx<crate::lambda::Term> {
+  use crate::lambda::*;
+  let mark = heap.root_mark();
+  let zero = heap.init(TermRepr::Var(0).into()).unwrap();
+  let x = heap.root(zero).unwrap();
+  for _ in 0..n {
+    let f = heap.init(TermRepr::Var(1).into()).unwrap();
+    let app = heap.init(TermRepr::App(f, heap.rooted(x)).into()).unwrap();
+    heap.set_root(x, app);
+  }
+  let body = heap.init(TermRepr::Lam(heap.rooted(x)).into()).unwrap();
+  let ret = heap.init(TermRepr::Lam(body).into()).unwrap();
+  heap.unroot(mark);
+  ret
+}
+
+/// the `n` of a church numeral built like `numeral` does, if `at` is one
+#[cfg(test)]
+fn count<const P: usize, const G: usize>(
+  heap : &crate::heap::Heap<crate::lambda::Term, P, G>,
+  at : crate::heap::Idx<crate::lambda::Term>,
+) -> Option<usize> {
+  use crate::lambda::*;
+  let repr = |at| TermRepr::from(*heap.get(heap.follow(at).unwrap()).unwrap());
+  let TermRepr::Lam(e) = repr(at) else {
+    return None;
+  };
+  let TermRepr::Lam(mut e) = repr(e) else {
+    return None;
+  };
+  let mut n = 0;
+  loop {
+    match repr(e) {
+      TermRepr::Var(0) => return Some(n),
+      TermRepr::App(f, x) if matches!(repr(f), TermRepr::Var(1)) => {
+        n += 1;
+        e = x;
+      }
+      _ => return None,
+    }
+  }
+}
+
+/// runs `f` on a stack far too small to recurse once per node of the terms
+/// below
+#[cfg(test)]
+fn on_small_stack(f : impl FnOnce() + Send + 'static) {
+  std::thread::Builder::new()
+    .stack_size(64 * 1024)
+    .spawn(f)
+    .unwrap()
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_deep_duplicate() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut src : Box<Heap<Term, 4096, 15>> = Box::default();
+  let mut heap : Box<Heap<Term, 4096, 15>> = Box::default();
+  on_small_stack(move || {
+    let n = numeral(&mut src, 5000);
+    let copy = heap.duplicate_from(&src, n).unwrap();
+    let r = heap.root(copy).unwrap();
+    let copy = heap.duplicate(copy).unwrap();
+    assert_eq!(count(&heap, heap.rooted(r)), Some(5000));
+    assert_eq!(count(&heap, copy), Some(5000));
+    assert!(heap.closed(copy, 0).unwrap());
+    assert_eq!(heap.redux(copy).unwrap(), None);
+    assert_eq!(heap.head(copy).unwrap(), None);
+    // `f` becomes free once shifted past the outer binder
+    let TermRepr::Lam(inner) = TermRepr::from(*heap.get(copy).unwrap()) else {
+      panic!()
+    };
+    heap.shift(inner, 0, 1).unwrap();
+    assert!(!heap.closed(copy, 0).unwrap());
+    assert!(heap.closed(copy, 1).unwrap());
+    assert_eq!(count(&heap, copy), None);
+  });
+}
+
+#[test]
+fn test_deep_lambdas() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Box<Heap<Term, 4096, 15>> = Box::default();
+  on_small_stack(move || {
+    // λ…λ(λ0 9999) under 10000 binders: closed, with its only redex at the bottom
+    let zero = heap.init(TermRepr::Var(0).into()).unwrap();
+    let id = heap.init(TermRepr::Lam(zero).into()).unwrap();
+    let redex = heap.root(id).unwrap();
+    let var = heap.init(TermRepr::Var(9999).into()).unwrap();
+    let mut t = heap
+      .init(TermRepr::App(heap.rooted(redex), var).into())
+      .unwrap();
+    heap.set_root(redex, t);
+    for _ in 0..10000 {
+      t = heap.init(TermRepr::Lam(t).into()).unwrap();
+    }
+    assert!(heap.closed(t, 0).unwrap());
+    assert_eq!(heap.redux(t).unwrap(), Some(heap.rooted(redex)));
+    assert_eq!(heap.head(t).unwrap(), Some(heap.rooted(redex)));
+    let t = heap.nf(t).unwrap();
+    let mut e = t;
+    for _ in 0..10000 {
+      let TermRepr::Lam(body) = TermRepr::from(*heap.get(e).unwrap()) else {
+        panic!()
+      };
+      e = body;
+    }
+    assert!(matches!(
+      TermRepr::from(*heap.get(e).unwrap()),
+      TermRepr::Var(9999)
+    ));
+  });
+}
+
+#[test]
+fn test_deep_replace() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Box<Heap<Term, 4096, 15>> = Box::default();
+  on_small_stack(move || {
+    // `λ(n 0)` contracts back to `n`, substituting the open `0` all the way down
+    let n = numeral(&mut heap, 5000);
+    let r = heap.root(n).unwrap();
+    let zero = heap.init(TermRepr::Var(0).into()).unwrap();
+    let app = heap
+      .init(TermRepr::App(heap.rooted(r), zero).into())
+      .unwrap();
+    let t = heap.init(TermRepr::Lam(app).into()).unwrap();
+    let t = heap.nf(t).unwrap();
+    assert_eq!(count(&heap, t), Some(5000));
+    // `f` is the closed identity in `n id`, so its occurrences are shared
+    let n = numeral(&mut heap, 5000);
+    heap.set_root(r, n);
+    let zero = heap.init(TermRepr::Var(0).into()).unwrap();
+    let id = heap.init(TermRepr::Lam(zero).into()).unwrap();
+    let app = heap.init(TermRepr::App(heap.rooted(r), id).into()).unwrap();
+    heap.set_root(r, app);
+    assert!(heap.beta(app).unwrap());
+    let TermRepr::Lam(mut e) = TermRepr::from(*heap.get(heap.rooted(r)).unwrap()) else {
+      panic!()
+    };
+    let mut ids = vec![];
+    while let TermRepr::App(f, x) = TermRepr::from(*heap.get(e).unwrap()) {
+      ids.push(f);
+      e = x;
+    }
+    assert_eq!(ids.len(), 5000);
+    assert!(ids.iter().all(|&f| f == ids[0]));
+    assert_eq!(show(&heap, ids[0]), "λ0");
+  });
+}
+
+/// counts the allocations each thread makes, so tests can check that
+/// something runs without touching the system heap
+#[cfg(test)]
+mod counting {
+  use std::alloc::{GlobalAlloc, Layout, System};
+  use std::cell::Cell;
+
+  std::thread_local! {
+    pub static ALLOCS : Cell<usize> = const { Cell::new(0) };
+  }
+
+  struct Counting;
+
+  unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout : Layout) -> *mut u8 {
+      let _ = ALLOCS.try_with(|n| n.set(n.get() + 1));
+      unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr : *mut u8, layout : Layout) {
+      unsafe { System.dealloc(ptr, layout) }
+    }
+  }
+
+  #[global_allocator]
+  static GLOBAL : Counting = Counting;
+}
+
+#[test]
+fn test_collect_without_allocating() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  use counting::ALLOCS;
+  let mut heap : Heap<Term, 64, 2> = Heap::new();
+  let t = build(&mut heap, "λλ(1 (1 0))");
+  let t = heap.root(t).unwrap();
+  // the first collection boxes a g1 page, later ones stay on it
+  heap.collect_g2(&mut []).unwrap();
+  let before = ALLOCS.with(|n| n.get());
+  let x = heap.init(TermRepr::Var(0).into()).unwrap();
+  let mut y = [heap.init(TermRepr::Lam(x).into()).unwrap()];
+  heap.collect_g2(&mut y).unwrap();
+  // g2 fills up a few times over, with young references in every value
+  for _ in 0..300 {
+    let hole = heap.init(TermRepr::Hole.into()).unwrap();
+    y[0] = heap
+      .init(TermRepr::App(hole, heap.rooted(t)).into())
+      .unwrap();
+  }
+  assert_eq!(ALLOCS.with(|n| n.get()), before);
+}
+
+#[test]
+fn test_term_packs_two_indices() {
+  use crate::lambda::*;
+  assert_eq!(core::mem::size_of::<Term>(), 2 * core::mem::size_of::<U>());
+}
+
+#[cfg(not(feature = "u32-index"))]
+#[test]
+#[should_panic(expected = "heap too large")]
+fn test_heap_too_large() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let _ : Box<Heap<Term, 4096, 16>> = Box::default();
+}
+
+#[cfg(feature = "u32-index")]
+#[test]
+fn test_large_heap() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  // far more than a `u16` can index
+  let mut heap : Box<Heap<Term, 16384, 15>> = Box::default();
+  let n = numeral(&mut heap, 50000);
+  let n = heap.root(n).unwrap();
+  let copy = heap.duplicate(heap.rooted(n)).unwrap();
+  assert!(copy.raw > u16::MAX as U);
+  assert_eq!(count(&heap, copy), Some(50000));
+  assert_eq!(count(&heap, heap.rooted(n)), Some(50000));
+}
+
+#[test]
+fn test_verify() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 8, 8> = Heap::new();
+  let t = build(&mut heap, "λ(λ0 (0 λ1))");
+  let r = heap.root(t).unwrap();
+  assert_eq!(heap.verify(), Ok(8));
+  // garbage is not reachable, and not checked either
+  heap.init(TermRepr::Lam(1.into()).into()).unwrap();
+  assert_eq!(heap.verify(), Ok(8));
+  let t = heap.nf(heap.rooted(r)).unwrap();
+  heap.set_root(r, t);
+  assert_eq!(show(&heap, t), "λ(0 λ1)");
+  // the body is an `Ind` to the argument it reduced to
+  assert_eq!(heap.verify(), Ok(6));
+  // point the body's argument somewhere unallocated
+  let TermRepr::Lam(body) = TermRepr::from(*heap.get(t).unwrap()) else {
+    panic!()
+  };
+  let body = heap.follow(body).unwrap();
+  let TermRepr::App(l, _) = TermRepr::from(*heap.get(body).unwrap()) else {
+    panic!()
+  };
+  *heap.get_mut(body).unwrap() = TermRepr::App(l, 63.into()).into();
+  assert_eq!(
+    heap.verify(),
+    Err(Corruption {
+      holder : body.raw,
+      error : HeapError::Dangling(63)
+    })
+  );
+}
+
+#[test]
+fn test_dump() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 4, 2> = Heap::new();
+  let x = heap.init(TermRepr::Var(0).into()).unwrap();
+  let t = heap.init(TermRepr::Lam(x).into()).unwrap();
+  heap.root(t).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  heap.init(TermRepr::Hole.into()).unwrap();
+  let mut out = String::new();
+  heap.dump(&mut out).unwrap();
+  assert_eq!(
+    out,
+    "g2: 1/3 used\n\
+     g1: page 0/2 at 3, 4 free\n\
+     roots: 1:0\n\
+     page 0:\n  1: Lam(Idx(2))\n  2: Var(0)\n\
+     g2:\n  9: Hole\n"
+  );
+}
+
+#[test]
+fn test_cons() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 8, 16> = Heap::new();
+  let f = heap.cons(TermRepr::Var(1).into()).unwrap();
+  let x = heap.cons(TermRepr::Var(0).into()).unwrap();
+  assert_eq!(heap.cons(TermRepr::Var(1).into()).unwrap(), f);
+  let fx = heap.cons(TermRepr::App(f, x).into()).unwrap();
+  assert_eq!(heap.cons(TermRepr::App(f, x).into()).unwrap(), fx);
+  assert_ne!(heap.cons(TermRepr::App(x, f).into()).unwrap(), fx);
+  // plain `init` does not share
+  assert_ne!(heap.init(TermRepr::Var(1).into()).unwrap(), f);
+}
+
+#[test]
+fn test_intern_across_collections() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut src : Box<Heap<Term, 4096, 0>> = Box::default();
+  let n = numeral(&mut src, 50);
+  // a tiny nursery, so interning runs through a lot of collections
+  let mut heap : Heap<Term, 8, 64> = Heap::new();
+  let a = heap.intern_from(&src, n).unwrap();
+  let a = heap.root(a).unwrap();
+  // every `(1 _)` has its own argument, but all of them share one `1`
+  assert_eq!(heap.verify(), Ok(50 + 4));
+  let b = heap.intern_from(&src, n).unwrap();
+  assert_eq!(b, heap.rooted(a));
+  let c = heap.duplicate(b).unwrap();
+  assert_ne!(c, b);
+  assert_eq!(heap.intern(c).unwrap(), heap.rooted(a));
+  assert_eq!(count(&heap, heap.rooted(a)), Some(50));
+}
+
+#[test]
+fn test_set_remembers_old_objects() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 8, 8> = Heap::new();
+  let old = heap.init(TermRepr::Hole.into()).unwrap();
+  let mut old = [old];
+  heap.collect_g2(&mut old).unwrap();
+  let young = heap.init(TermRepr::Var(5).into()).unwrap();
+  heap.set(old[0], TermRepr::Lam(young).into()).unwrap();
+  // no promotion on `set`, the young object stays where it is
+  assert_eq!(show(&heap, old[0]), "λ5");
+  assert!(matches!(TermRepr::from(*heap.get(old[0]).unwrap()), TermRepr::Lam(e) if e == young));
+  // only reachable through the (unrooted) old object
+  heap.collect_g2(&mut []).unwrap();
+  assert_eq!(show(&heap, old[0]), "λ5");
+  heap.collect_g2(&mut []).unwrap();
+  assert_eq!(show(&heap, old[0]), "λ5");
+}
+
+#[test]
+fn test_remembered_set_overflow() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 64, 8> = Heap::new();
+  let mut old = [0.into(); 40];
+  for o in old.iter_mut() {
+    *o = heap.init(TermRepr::Hole.into()).unwrap();
+  }
+  heap.collect_g2(&mut old).unwrap();
+  // more old objects written through `get_mut` than the remembered set holds
+  for (i, &o) in old.iter().enumerate() {
+    let young = heap.init(TermRepr::Var(i as U).into()).unwrap();
+    *heap.get_mut(o).unwrap() = TermRepr::Lam(young).into();
+  }
+  heap.collect_g2(&mut []).unwrap();
+  for (i, &o) in old.iter().enumerate() {
+    assert_eq!(show(&heap, o), format!("λ{i}"));
+  }
+}
+
+#[test]
+fn test_collect_g1_reclaims_pages() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 16, 8> = Heap::new();
+  let keep = build(&mut heap, "λ(0 λ1)");
+  let keep = heap.root(keep).unwrap();
+  // two numerals worth of pages, only one of them still wanted afterwards
+  let mark = heap.root_mark();
+  let n = numeral(&mut heap, 20);
+  heap.root(n).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  heap.unroot(mark);
+  let n = numeral(&mut heap, 20);
+  let n = heap.root(n).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  let free = heap.g1_free();
+  assert_eq!(heap.collect_g1(&mut []).unwrap(), 3);
+  assert_eq!(heap.g1_free(), free + 43);
+  assert_eq!(heap.verify(), Ok(5 + 43));
+  assert_eq!(show(&heap, heap.rooted(keep)), "λ(0 λ1)");
+  assert_eq!(count(&heap, heap.rooted(n)), Some(20));
+  // nothing left to take back
+  assert_eq!(heap.collect_g1(&mut []).unwrap(), 0);
+  assert_eq!(heap.verify(), Ok(5 + 43));
+}
+
+#[test]
+fn test_collect_g1_fixes_up_everything() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut src : Box<Heap<Term, 4096, 0>> = Box::default();
+  let n = numeral(&mut src, 10);
+  let mut heap : Heap<Term, 8, 16> = Heap::new();
+  // garbage at the bottom of g1, so everything above moves down
+  let garbage = heap.duplicate_from(&src, n).unwrap();
+  heap.collect_g2(&mut [garbage]).unwrap();
+  let a = heap.intern_from(&src, n).unwrap();
+  let a = heap.root(a).unwrap();
+  let mut old = [heap.init(TermRepr::Hole.into()).unwrap()];
+  heap.collect_g2(&mut old).unwrap();
+  let young = heap.init(TermRepr::Var(3).into()).unwrap();
+  heap.set(old[0], TermRepr::Lam(young).into()).unwrap();
+  assert!(heap.collect_g1(&mut old).unwrap() > 0);
+  // interned terms still get found, remembered slots still get scanned
+  assert_eq!(heap.intern_from(&src, n).unwrap(), heap.rooted(a));
+  heap.collect_g2(&mut old).unwrap();
+  assert_eq!(show(&heap, old[0]), "λ3");
+  assert_eq!(count(&heap, heap.rooted(a)), Some(10));
+}
+
+#[test]
+fn test_nf_collects_g1() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let power = "λλλλ(((2 3) 1) 0)";
+  // far less g1 than everything the reduction promotes over its run
+  let mut heap : Heap<Term, 32, 8> = Heap::new();
+  let t = build(
+    &mut heap,
+    &format!("(({power} {}) {})", church(3), church(3)),
+  );
+  let t = heap.nf(t).unwrap();
+  assert_eq!(count(&heap, t), Some(27));
+}
+
+#[test]
+fn test_collect_step_reclaims_slots() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 16, 8> = Heap::new();
+  let keep = build(&mut heap, "λ(0 λ1)");
+  let keep = heap.root(keep).unwrap();
+  let mark = heap.root_mark();
+  let n = numeral(&mut heap, 20);
+  heap.root(n).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  heap.unroot(mark);
+  let n = numeral(&mut heap, 20);
+  let n = heap.root(n).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  let free = heap.g1_free();
+  let mut slices = 1;
+  while !heap.collect_step(4).unwrap() {
+    slices += 1;
+  }
+  assert!(slices > 10);
+  assert_eq!(heap.phase(), Phase::Idle);
+  assert_eq!(heap.g1_free(), free + 43);
+  assert_eq!(heap.verify(), Ok(5 + 43));
+  assert_eq!(show(&heap, heap.rooted(keep)), "λ(0 λ1)");
+  assert_eq!(count(&heap, heap.rooted(n)), Some(20));
+  // the next promotion fills the freed slots before any new ones
+  let m = numeral(&mut heap, 20);
+  let m = heap.root(m).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  assert_eq!(heap.g1_free(), free);
+  assert_eq!(heap.verify(), Ok(5 + 43 + 43));
+  assert_eq!(count(&heap, heap.rooted(n)), Some(20));
+  assert_eq!(count(&heap, heap.rooted(m)), Some(20));
+}
+
+#[test]
+fn test_collect_step_barriers() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 16, 8> = Heap::new();
+  let t = build(&mut heap, "λ(λ(0 1) λ0)");
+  let t = heap.root(t).unwrap();
+  let mark = heap.root_mark();
+  let id = build(&mut heap, "λ0");
+  let id = heap.intern(id).unwrap();
+  let id = heap.root(id).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  let id = heap.rooted(id);
+  heap.unroot(mark);
+  assert!(!heap.collect_step(1).unwrap());
+  assert_eq!(heap.phase(), Phase::Mark);
+  // move the left half to a root taken after the snapshot, then cut it out of
+  // the term it was reachable through
+  let TermRepr::Lam(app) = TermRepr::from(*heap.get(heap.rooted(t)).unwrap()) else {
+    panic!()
+  };
+  let TermRepr::App(l, r) = TermRepr::from(*heap.get(app).unwrap()) else {
+    panic!()
+  };
+  let l = heap.root(l).unwrap();
+  heap.set(app, TermRepr::App(r, r).into()).unwrap();
+  // and the right half through `get_mut`
+  let r = heap.root(r).unwrap();
+  *heap.get_mut(app).unwrap() = TermRepr::Var(0).into();
+  // the interned copy is unreachable, until interning finds it again
+  let again = build(&mut heap, "λ0");
+  let again = heap.intern(again).unwrap();
+  assert_eq!(again, id);
+  let id = heap.root(id).unwrap();
+  while !heap.collect_step(1).unwrap() {}
+  assert_eq!(heap.verify(), Ok(1 + 1 + 4 + 2 + 2));
+  assert_eq!(show(&heap, heap.rooted(t)), "λ0");
+  assert_eq!(show(&heap, heap.rooted(l)), "λ(0 1)");
+  assert_eq!(show(&heap, heap.rooted(r)), "λ0");
+  assert_eq!(show(&heap, heap.rooted(id)), "λ0");
+}
+
+#[test]
+fn test_reduce_between_collect_steps() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let power = "λλλλ(((2 3) 1) 0)";
+  let mut heap : Heap<Term, 32, 8> = Heap::new();
+  let t = build(
+    &mut heap,
+    &format!("(({power} {}) {})", church(3), church(3)),
+  );
+  let t = heap.root(t).unwrap();
+  let mut cycles = 0;
+  while let Some(redex) = heap.redux(heap.rooted(t)).unwrap() {
+    heap.beta(redex).unwrap();
+    if heap.collect_step(8).unwrap() {
+      cycles += 1;
+      heap.verify().unwrap();
+    }
+  }
+  assert!(cycles > 0);
+  assert_eq!(count(&heap, heap.rooted(t)), Some(27));
+}
+
+#[test]
+fn test_static_heap() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  use core::mem::MaybeUninit;
+  use counting::ALLOCS;
+  fn leak<X>(len : usize) -> &'static mut [MaybeUninit<X>] { Box::leak(Box::new_uninit_slice(len)) }
+  let power = format!("(({} {}) {})", "λλλλ(((2 3) 1) 0)", church(3), church(2));
+  let memory = Memory {
+    nursery : &mut leak(1)[0],
+    pages : leak(8),
+    roots : leak(256),
+    marks : leak(9),
+    gray : leak(256),
+    conses : [leak(16), leak(16)],
+  };
+  let mut heap : Heap<Term, 32, 8> = Heap::new_static(memory);
+  // `build` goes through a boxed scratch heap, everything after stays put
+  let t = build(&mut heap, &power);
+  let before = ALLOCS.with(|n| n.get());
+  let t = heap.nf(t).unwrap();
+  let t = heap.root(t).unwrap();
+  assert_eq!(count(&heap, heap.rooted(t)), Some(9));
+  // pages go back to the reserve and are taken from there again
+  assert!(heap.collect_g1(&mut []).unwrap() > 0);
+  let n = numeral(&mut heap, 100);
+  assert_eq!(count(&heap, n), Some(100));
+  assert_eq!(count(&heap, heap.rooted(t)), Some(9));
+  // a table of 16 holds 8 conses
+  for i in 0..8 {
+    let var = heap.cons(TermRepr::Var(i).into()).unwrap();
+    heap.root(var).unwrap();
+  }
+  assert_eq!(
+    heap.cons(TermRepr::Var(8).into()),
+    Err(HeapError::OutOfMemory)
+  );
+  assert_eq!(ALLOCS.with(|n| n.get()), before);
+}
+
+#[test]
+fn test_static_stacks() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  use core::mem::MaybeUninit;
+  fn leak<X>(len : usize) -> &'static mut [MaybeUninit<X>] { Box::leak(Box::new_uninit_slice(len)) }
+  let memory = Memory {
+    nursery : &mut leak(1)[0],
+    pages : leak(8),
+    roots : leak(8),
+    marks : leak(9),
+    gray : leak(2),
+    conses : [leak(16), leak(16)],
+  };
+  let mut heap : Heap<Term, 32, 8> = Heap::new_static(memory);
+  // copying keeps a frame per node down the right spine
+  let n = numeral(&mut heap, 20);
+  let base = heap.root_mark();
+  assert_eq!(heap.duplicate(n), Err(HeapError::OutOfMemory));
+  assert_eq!(heap.root_mark(), base);
+  let mut vars = vec![];
+  for i in 0..5 {
+    let var = heap.init(TermRepr::Var(i).into()).unwrap();
+    vars.push(heap.root(var).unwrap());
+  }
+  heap.collect_g2(&mut []).unwrap();
+  // marking pushes each of the 5 roots once more, and shading needs a gray
+  // slot for each
+  assert_eq!(heap.collect_g1(&mut []), Err(HeapError::OutOfMemory));
+  assert_eq!(heap.collect_step(8), Err(HeapError::OutOfMemory));
+  assert_eq!(heap.phase(), Phase::Idle);
+  assert_eq!(heap.verify(), Ok(5));
+  assert_eq!(show(&heap, heap.rooted(vars[4])), "4");
+  for _ in 5..8 {
+    heap.root(0.into()).unwrap();
+  }
+  assert_eq!(heap.root(0.into()), Err(HeapError::OutOfMemory));
+  // with two roots left, both kinds of major collection get through
+  heap.unroot(vars[2]);
+  heap.collect_g1(&mut []).unwrap();
+  while !heap.collect_step(8).unwrap() {}
+  assert_eq!(heap.verify(), Ok(2));
+  assert_eq!(show(&heap, heap.rooted(vars[1])), "1");
+}
+
+#[test]
+#[cfg(feature = "checked-idx")]
+fn test_stale_index() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 16, 8> = Heap::new();
+  let old = heap.init(TermRepr::Var(0).into()).unwrap();
+  let young = heap.init(TermRepr::Lam(old).into()).unwrap();
+  let root = heap.root(young).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  assert_eq!(heap.get(young), Err(HeapError::Stale(young.raw)));
+  assert_eq!(
+    heap.set(young, TermRepr::Hole.into()),
+    Err(HeapError::Stale(young.raw))
+  );
+  // what the heap hands out after the collection is fine, and so is what is
+  // read out of objects
+  let young = heap.rooted(root);
+  let TermRepr::Lam(old) = TermRepr::from(*heap.get(young).unwrap()) else {
+    panic!()
+  };
+  assert_eq!(heap.get(old), Ok(&TermRepr::Var(0).into()));
+  // g1 only moves in major collections
+  let new = heap.init(TermRepr::Hole.into()).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  assert!(heap.get(young).is_ok());
+  assert_eq!(heap.get(new), Err(HeapError::Stale(new.raw)));
+  heap.collect_g1(&mut []).unwrap();
+  assert_eq!(heap.get(young), Err(HeapError::Stale(young.raw)));
+  assert_eq!(show(&heap, heap.rooted(root)), "λ0");
+  assert_eq!(
+    HeapError::Stale(7).to_string(),
+    "stale index 7, from before a collection"
+  );
+}
+
+#[test]
+fn test_collect_step_keeps_indices() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 16, 8> = Heap::new();
+  let t = build(&mut heap, "λ(0 λ1)");
+  let t = heap.root(t).unwrap();
+  let garbage = numeral(&mut heap, 5);
+  let garbage = heap.root(garbage).unwrap();
+  heap.collect_g2(&mut []).unwrap();
+  heap.unroot(garbage);
+  // handed out after the promotion, so it points into g1
+  let live = heap.rooted(t);
+  while !heap.collect_step(4).unwrap() {}
+  assert!(heap.get(live).is_ok());
+  assert_eq!(show(&heap, live), "λ(0 λ1)");
+}
+
+#[test]
+fn test_beta_shares_result() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 64, 4> = Heap::new();
+  // the argument is a redex itself, so copying it would duplicate it
+  let t = build(&mut heap, "(λ0 (λ0 λ1))");
+  let TermRepr::App(_, arg) = TermRepr::from(*heap.get(t).unwrap()) else {
+    panic!()
+  };
+  assert!(heap.beta(t).unwrap());
+  assert!(matches!(TermRepr::from(*heap.get(t).unwrap()), TermRepr::Ind(to) if to == arg));
+  assert_eq!(heap.follow(t), Ok(arg));
+  // reducing it through the indirection reduces the one shared node
+  assert!(heap.beta(heap.follow(t).unwrap()).unwrap());
+  assert_eq!(show(&heap, t), "λ1");
+  assert_eq!(show(&heap, arg), "λ1");
+  // a body that ignores its argument is shared too, everything else is a
+  // fresh node moved into place
+  let t = build(&mut heap, "(λ(λ0 λ0) 5)");
+  assert!(heap.beta(t).unwrap());
+  assert!(matches!(
+    TermRepr::from(*heap.get(t).unwrap()),
+    TermRepr::Ind(_)
+  ));
+  let t = build(&mut heap, "(λ(0 0) 3)");
+  assert!(heap.beta(t).unwrap());
+  assert!(matches!(
+    TermRepr::from(*heap.get(t).unwrap()),
+    TermRepr::App(..)
+  ));
+  assert_eq!(show(&heap, t), "(3 3)");
+}
+
+#[test]
+fn test_whnf() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let mut heap : Heap<Term, 64, 4> = Heap::new();
+  // under a binder and in arguments nothing happens
+  for src in ["λ(λ0 0)", "(0 (λ0 1))", "5"] {
+    let t = build(&mut heap, src);
+    let t = heap.whnf(t).unwrap();
+    assert_eq!(show(&heap, t), src);
+  }
+  let t = build(&mut heap, "((λλ(0 1) λ0) 7)");
+  let t = heap.whnf(t).unwrap();
+  assert_eq!(show(&heap, t), "(7 λ0)");
+  // `(λ(0 0) (I I))`: the argument is reduced where it is first needed, and
+  // its second use finds it done
+  let t = build(&mut heap, "(λ(0 0) (λ0 λ0))");
+  let TermRepr::App(_, arg) = TermRepr::from(*heap.get(t).unwrap()) else {
+    panic!()
+  };
+  let arg = heap.root(arg).unwrap();
+  let t = heap.whnf(t).unwrap();
+  assert_eq!(show(&heap, t), "λ0");
+  let arg = heap.rooted(arg);
+  assert!(matches!(
+    TermRepr::from(*heap.get(arg).unwrap()),
+    TermRepr::Lam(_)
+  ));
+}
+
+#[test]
+fn test_nf_shares_power() {
+  use crate::heap::*;
+  use crate::lambda::*;
+  let power = "λλλλ(((2 3) 1) 0)";
+  let mut heap : Heap<Term, 1024, 32> = Heap::new();
+  let t = build(
+    &mut heap,
+    &format!("(({power} {}) {})", church(2), church(10)),
+  );
+  let t = heap.nf(t).unwrap();
+  assert_eq!(count(&heap, t), Some(1024));
+}
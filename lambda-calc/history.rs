@@ -0,0 +1,125 @@
+//! a term together with the steps taken on it, to go back and forth through.
+//! instead of snapshots, each step keeps where its redex was and the redex as
+//! it was before: putting that back undoes the step, and contracting it again
+//! redoes it. the redexes held are capped by their size, and the oldest steps
+//! are forgotten to stay under it.
+extern crate alloc;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::prelude::rust_2024::*;
+
+use crate::lambda::Expr;
+use crate::strategy::{Dir, StepResult, Strategy};
+
+struct Entry {
+  path : Vec<Dir>,
+  redex : Expr,
+}
+
+impl Entry {
+  fn size(&self) -> usize { self.path.len() + self.redex.size() }
+}
+
+pub struct History {
+  term : Expr,
+  entries : VecDeque<Entry>,
+  /// how many of `entries` are done to `term`, the rest are there to redo
+  done : usize,
+  /// steps forgotten off the front
+  forgotten : usize,
+  /// `Entry::size` of all of `entries`
+  nodes : usize,
+  limit : usize,
+}
+
+impl History {
+  /// keep at most `limit` nodes, as counted by `Expr::size` plus one for
+  /// each `Dir`, worth of steps.
+  pub fn new(term : Expr, limit : usize) -> Self {
+    History {
+      term,
+      entries : VecDeque::new(),
+      done : 0,
+      forgotten : 0,
+      nodes : 0,
+      limit,
+    }
+  }
+
+  pub fn term(&self) -> &Expr { &self.term }
+  /// how many steps it took to get to `term`
+  pub fn at(&self) -> usize { self.forgotten + self.done }
+  /// the earliest step still remembered
+  pub fn first(&self) -> usize { self.forgotten }
+  /// the latest step there is to go forward to
+  pub fn last(&self) -> usize { self.forgotten + self.entries.len() }
+  /// where the redex of the step leading to `term` was
+  pub fn last_path(&self) -> Option<&[Dir]> {
+    self.done.checked_sub(1).map(|i| &self.entries[i].path[..])
+  }
+
+  /// take a new step, dropping any that were undone.
+  pub fn step(&mut self, strategy : Strategy) -> StepResult {
+    let Some(path) = self.term.redex_path(strategy) else {
+      return StepResult::Done;
+    };
+    for entry in self.entries.drain(self.done..) {
+      self.nodes -= entry.size();
+    }
+    let at = self.term.at_path(&path).unwrap();
+    let redex = at.clone();
+    at.beta();
+    let entry = Entry {
+      path : path.clone(),
+      redex,
+    };
+    self.nodes += entry.size();
+    self.entries.push_back(entry);
+    self.done += 1;
+    while self.nodes > self.limit {
+      let Some(entry) = self.entries.pop_front() else {
+        break;
+      };
+      self.nodes -= entry.size();
+      self.done -= 1;
+      self.forgotten += 1;
+    }
+    StepResult::Contracted(path)
+  }
+
+  /// undo the step leading to `term`. `false` if there is none remembered.
+  pub fn back(&mut self) -> bool {
+    let Some(i) = self.done.checked_sub(1) else {
+      return false;
+    };
+    let entry = &self.entries[i];
+    *self.term.at_path(&entry.path).unwrap() = entry.redex.clone();
+    self.done = i;
+    true
+  }
+
+  /// redo an undone step. `false` if there is none.
+  pub fn forward(&mut self) -> bool {
+    let Some(entry) = self.entries.get(self.done) else {
+      return false;
+    };
+    self.term.at_path(&entry.path).unwrap().beta();
+    self.done += 1;
+    true
+  }
+
+  /// go back or forward to `step`. `false`, without moving, if it is
+  /// outside `first..=last`.
+  pub fn jump(&mut self, step : usize) -> bool {
+    if !(self.first()..=self.last()).contains(&step) {
+      return false;
+    }
+    while self.at() > step {
+      self.back();
+    }
+    while self.at() < step {
+      self.forward();
+    }
+    true
+  }
+}
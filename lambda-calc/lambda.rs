@@ -1,10 +1,14 @@
 extern crate alloc;
-use alloc::{boxed::Box, fmt::Display};
+use alloc::{boxed::Box, fmt::Display, vec, vec::Vec};
 use core::prelude::rust_2024::*;
-use core::{char, fmt, matches, mem, u32, usize, write};
+use core::{char, fmt, matches, mem, write};
+use lambda_arena::heap::{Heap, HeapError, Idx, Root};
+use lambda_arena::lambda::{Term, TermRepr, U};
 use once_cell::sync::Lazy;
 
-#[derive(Clone, Debug, PartialEq, Eq, Default)]
+use crate::ski::{Basis, Ski};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub enum Expr {
   Var(u32),
   Lam(Box<Expr>),
@@ -131,7 +135,7 @@ impl Expr {
   #[must_use]
   pub fn closed(&self, v : u32) -> bool {
     match self {
-      Var(u) => *u <= v,
+      Var(u) => *u < v,
       Lam(e) => e.closed(v + 1),
       App(l, r) => l.closed(v) && r.closed(v),
       _ => true,
@@ -298,8 +302,136 @@ impl Expr {
     }
   }
 }
-const VAR_NUMERALS : [char; 11] = ['🄌', '➊', '➋', '➌', '➍', '➎', '➏', '➐', '➑', '➒', '➓'];
-const VAR_LEAF : [char; 11] = ['🄋', '➀', '➁', '➂', '➃', '➄', '➅', '➆', '➇', '➈', '➉'];
+/// why an `Expr` did not make it into a `Heap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaError {
+  Heap(HeapError),
+  /// the editor cursor, `replace_slot` it before converting
+  Slot,
+  /// only ever exists in the middle of `beta`
+  Thunk,
+  /// index does not fit the arena's `U`
+  VarTooLarge(u32),
+}
+
+impl From<HeapError> for ArenaError {
+  fn from(value : HeapError) -> Self { ArenaError::Heap(value) }
+}
+
+impl Display for ArenaError {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ArenaError::Heap(e) => write!(f, "{e}"),
+      ArenaError::Slot => write!(f, "unfilled slot"),
+      ArenaError::Thunk => write!(f, "stray thunk"),
+      ArenaError::VarTooLarge(u) => write!(f, "[{u}] too large"),
+    }
+  }
+}
+
+impl Expr {
+  /// copy into `heap`, mapping node for node (`Hole` included). the result
+  /// is not rooted.
+  pub fn to_arena<const PAGESIZE: usize, const G1PAGES: usize>(
+    &self,
+    heap : &mut Heap<Term, PAGESIZE, G1PAGES>,
+  ) -> Result<Idx<Term>, ArenaError> {
+    let base = heap.root_mark();
+    let ret = self.to_arena_(heap, base);
+    heap.unroot(base);
+    ret
+  }
+  fn to_arena_<const PAGESIZE: usize, const G1PAGES: usize>(
+    &self,
+    heap : &mut Heap<Term, PAGESIZE, G1PAGES>,
+    base : Root,
+  ) -> Result<Idx<Term>, ArenaError> {
+    // subterms still to be copied, and whether their children already are.
+    // copied children wait on the root stack, as allocating could move them.
+    let mut todo = vec![(self, false)];
+    let child =
+      |heap : &mut Heap<Term, PAGESIZE, G1PAGES>| heap.pop_frame(base).expect("copied subterm").0;
+    while let Some((e, children)) = todo.pop() {
+      let repr = match (e, children) {
+        (Lam(b), false) => {
+          todo.extend([(e, true), (b, false)]);
+          continue;
+        }
+        (App(l, r), false) => {
+          todo.extend([(e, true), (r, false), (l, false)]);
+          continue;
+        }
+        (Lam(_), true) => TermRepr::Lam(child(heap)),
+        (App(..), true) => {
+          let r = child(heap);
+          TermRepr::App(child(heap), r)
+        }
+        // stored as `u + 1`, see `Term`
+        (Var(u), _) => match U::try_from(*u) {
+          Ok(u) if u != U::MAX => TermRepr::Var(u),
+          _ => return Err(ArenaError::VarTooLarge(*u)),
+        },
+        (Hole, _) => TermRepr::Hole,
+        (Slot, _) => return Err(ArenaError::Slot),
+        (Thunk(_), _) => return Err(ArenaError::Thunk),
+      };
+      let at = heap.init(repr.into())?;
+      heap.push_frame(at, 0)?;
+    }
+    Ok(child(heap))
+  }
+
+  pub fn from_arena<const PAGESIZE: usize, const G1PAGES: usize>(
+    heap : &Heap<Term, PAGESIZE, G1PAGES>,
+    at : Idx<Term>,
+  ) -> Result<Expr, HeapError> {
+    // like `to_arena`, but nothing moves, so the copied children can wait in
+    // a plain stack
+    let mut todo = vec![(at, false)];
+    let mut done = Vec::new();
+    while let Some((at, children)) = todo.pop() {
+      let e = match (TermRepr::from(*heap.get(at)?), children) {
+        (TermRepr::Lam(b), false) => {
+          todo.extend([(at, true), (b, false)]);
+          continue;
+        }
+        (TermRepr::App(l, r), false) => {
+          todo.extend([(at, true), (r, false), (l, false)]);
+          continue;
+        }
+        (TermRepr::Ind(to), _) => {
+          todo.push((to, false));
+          continue;
+        }
+        (TermRepr::Lam(_), true) => lam(done.pop().unwrap()),
+        (TermRepr::App(..), true) => {
+          let r = done.pop().unwrap();
+          app(done.pop().unwrap(), r)
+        }
+        (TermRepr::Hole, _) => Hole,
+        (TermRepr::Var(u), _) => Var(u.into()),
+      };
+      done.push(e);
+    }
+    Ok(done.pop().unwrap())
+  }
+
+  /// `nf` done in `heap` instead of on boxes. `heap` is left with garbage
+  /// only, nothing stays rooted.
+  pub fn nf_in<const PAGESIZE: usize, const G1PAGES: usize>(
+    &mut self,
+    heap : &mut Heap<Term, PAGESIZE, G1PAGES>,
+  ) -> Result<(), ArenaError> {
+    let at = self.to_arena(heap)?;
+    let at = heap.nf(at)?;
+    *self = Expr::from_arena(heap, at)?;
+    Ok(())
+  }
+}
+
+pub(crate) const VAR_NUMERALS : [char; 11] =
+  ['🄌', '➊', '➋', '➌', '➍', '➎', '➏', '➐', '➑', '➒', '➓'];
+pub(crate) const VAR_LEAF : [char; 11] = ['🄋', '➀', '➁', '➂', '➃', '➄', '➅', '➆', '➇', '➈', '➉'];
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum LeafMode {
@@ -308,31 +440,158 @@ pub enum LeafMode {
   InputDot,
 }
 
+/// what `DisplayStruct` writes terms as
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Notation {
+  /// lambdas and de Bruijn indices
+  DeBruijn,
+  /// lambdas binding made up names, `λx.λy.x y`
+  Named,
+  /// combinators, see `ski`. terms still being edited stay lambdas
+  Combinators(Basis),
+}
+
+impl Notation {
+  /// the one after this, for a key cycling through them
+  #[must_use]
+  pub fn next(self) -> Notation {
+    match self {
+      Notation::DeBruijn => Notation::Named,
+      Notation::Named => Notation::Combinators(Basis::Ski),
+      Notation::Combinators(Basis::Ski) => Notation::Combinators(Basis::Bcw),
+      Notation::Combinators(Basis::Bcw) => Notation::DeBruijn,
+    }
+  }
+}
+
+/// what `DisplayStruct` calls the closed terms it recognises
+fn constant(expr : &Expr) -> Option<&'static str> {
+  match expr {
+    _ if *ID == *expr => Some("I"),
+    _ if *CONST == *expr => Some("K"),
+    _ if *FORK == *expr => Some("S"),
+    _ if *SUCC == *expr => Some("SUCC"),
+    _ if *PLUS == *expr => Some("+"),
+    _ if *TIMES == *expr => Some("*"),
+    _ if *POWER == *expr => Some("^"),
+    _ => None,
+  }
+}
+
+/// how many variables `expr` has free past `depth` binders, counting up to
+/// the outermost one it uses. the cursor goes where the `Slot` is.
+fn free(expr : &Expr, cursor : &Expr, depth : u32) -> u64 {
+  match expr {
+    // `u64`, as `u32::MAX + 1` of them are possible
+    Var(u) => (u64::from(*u) + 1).saturating_sub(depth.into()),
+    Lam(e) => free(e, cursor, depth + 1),
+    App(l, r) => free(l, cursor, depth).max(free(r, cursor, depth)),
+    Slot => free(cursor, &Hole, depth),
+    Hole | Thunk(_) => 0,
+  }
+}
+
 #[derive(Debug)]
 pub struct DisplayStruct<'a> {
   pub expr : &'a Expr,
   pub cursor : &'a Expr,
   pub leaf_mode : LeafMode,
+  pub notation : Notation,
 }
 
 impl DisplayStruct<'_> {
   pub const CURSOR_START : char = '\u{e000}';
   pub const CURSOR_END : char = '\u{e001}';
+  /// stands for a `Thunk`, which only exists halfway through `beta`. in the
+  /// font, and not read by `parse`, unlike `▪`
+  pub const THUNK : &'static str = "«thunk»";
+
+  /// the `n`th of x, y, z, x1, y1, z1, x2, ...
+  fn name(f : &mut fmt::Formatter, n : u64) -> fmt::Result {
+    write!(f, "{}", ['x', 'y', 'z'][(n % 3) as usize])?;
+    if n >= 3 {
+      write!(f, "{}", n / 3)?;
+    }
+    Ok(())
+  }
+
+  /// `Notation::Named`. the variables free in the whole term are called by
+  /// the first `free` names, in order, and a binder under `depth` others
+  /// by the one `depth` after those: no name is ever captured. `wrap` puts
+  /// parentheses around what is not a single token.
+  fn fmt_named(
+    &self,
+    f : &mut fmt::Formatter,
+    depth : u32,
+    free : u64,
+    wrap : bool,
+  ) -> fmt::Result {
+    let sub = |expr| DisplayStruct { expr, ..*self };
+    if let Some(n) = self.expr.to_nat() {
+      return write!(f, "{n}");
+    }
+    if let Some(name) = constant(self.expr) {
+      return write!(f, "{name}");
+    }
+    match self.expr {
+      Var(u) if *u < depth => Self::name(f, free + u64::from(depth - 1 - u)),
+      Var(u) => Self::name(f, (u - depth).into()),
+      Lam(_) | App(..) if wrap => {
+        write!(f, "(")?;
+        self.fmt_named(f, depth, free, false)?;
+        write!(f, ")")
+      }
+      Lam(e) => {
+        write!(f, "λ")?;
+        Self::name(f, free + u64::from(depth))?;
+        write!(f, ".")?;
+        sub(e).fmt_named(f, depth + 1, free, false)
+      }
+      App(l, r) => {
+        sub(l).fmt_named(f, depth, free, matches!(**l, Lam(_)))?;
+        write!(f, " ")?;
+        sub(r).fmt_named(f, depth, free, true)
+      }
+      Hole => write!(f, "▪"),
+      Slot => {
+        if *self.cursor == Hole && self.leaf_mode == LeafMode::InputDot {
+          return write!(f, "⬤");
+        }
+        write!(f, "{}", Self::CURSOR_START)?;
+        DisplayStruct {
+          expr : self.cursor,
+          cursor : &Hole,
+          ..*self
+        }
+        .fmt_named(f, depth, free, false)?;
+        write!(f, "{}", Self::CURSOR_END)
+      }
+      Thunk(..) => write!(f, "{}", Self::THUNK),
+    }
+  }
 }
 
 impl Display for DisplayStruct<'_> {
   fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    if let Notation::Combinators(basis) = self.notation {
+      return match Ski::from_expr(self.expr, basis) {
+        Ok(ski) => write!(f, "{ski}"),
+        Err(_) => DisplayStruct {
+          notation : Notation::DeBruijn,
+          ..*self
+        }
+        .fmt(f),
+      };
+    }
+    if self.notation == Notation::Named {
+      return self.fmt_named(f, 0, free(self.expr, self.cursor, 0), false);
+    }
     if let Some(n) = self.expr.to_nat() {
       write!(f, "{n}")
+    } else if let Some(name) = constant(self.expr) {
+      write!(f, "{name}")
     } else {
       match self.expr {
-        _ if *ID == *self.expr => write!(f, "I"),
-        _ if *CONST == *self.expr => write!(f, "K"),
-        _ if *FORK == *self.expr => write!(f, "S"),
-        _ if *SUCC == *self.expr => write!(f, "SUCC"),
-        _ if *PLUS == *self.expr => write!(f, "+"),
-        _ if *TIMES == *self.expr => write!(f, "*"),
-        _ if *POWER == *self.expr => write!(f, "^"),
         Var(u) => {
           if *u <= 10 {
             write!(
@@ -383,18 +642,16 @@ impl Display for DisplayStruct<'_> {
           }
         }
         Hole => write!(f, "▪"),
-        Slot => {
-          if let Var(u) = self.cursor && self.leaf_mode == LeafMode::Leaf {
-            write!(f, "{}", VAR_LEAF[*u as usize])
-          } else if *self.cursor == Hole && self.leaf_mode == LeafMode::InputDot {
-            write!(f, "⬤")
-          } else {
+        Slot => match (self.cursor, self.leaf_mode) {
+          (Var(u), LeafMode::Leaf) => write!(f, "{}", VAR_LEAF[*u as usize]),
+          (Hole, LeafMode::InputDot) => write!(f, "⬤"),
+          _ => {
             write!(f, "{}", Self::CURSOR_START)?;
             self.cursor.fmt(f)?;
             write!(f, "{}", Self::CURSOR_END)
           }
-        }
-        Thunk(..) => todo!()
+        },
+        Thunk(..) => write!(f, "{}", Self::THUNK),
       }
     }
   }
@@ -407,6 +664,7 @@ impl Display for Expr {
       expr : self,
       cursor : &Hole,
       leaf_mode : LeafMode::No,
+      notation : Notation::DeBruijn,
     }
     .fmt(f)
   }
@@ -0,0 +1,18 @@
+//! the calculator's lambda terms and everything done with them, apart from
+//! the device: printing, parsing, the reducers and the reduction history.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![feature(box_patterns)]
+// under `u32-index` the arena index is `u32` itself, so widening it is a no-op
+#![cfg_attr(feature = "u32-index", allow(clippy::useless_conversion))]
+
+#[cfg(feature = "std")]
+pub mod test;
+
+pub mod history;
+pub mod lambda;
+pub mod machine;
+pub mod nbe;
+pub mod parse;
+pub mod ski;
+pub mod strategy;
+pub mod vm;
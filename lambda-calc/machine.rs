@@ -0,0 +1,336 @@
+//! environment machines for `Expr`. instead of substituting, a beta step
+//! binds the argument in an environment, and variables are looked up there
+//! when they are reached: nothing gets copied or shifted while running.
+//! both machines evaluate to weak head normal form, one transition per
+//! `step`, and `read_back` turns whatever state they are in into an `Expr`.
+extern crate alloc;
+use alloc::{rc::Rc, vec::Vec};
+use core::prelude::rust_2024::*;
+
+use crate::lambda::{app, lam, Expr};
+use Expr::{App, Lam, Var};
+
+/// bindings, innermost binder first. shared between all the closures made
+/// under the same binders.
+pub struct Env<T>(Option<Rc<(T, Env<T>)>>);
+
+impl<T> Env<T> {
+  #[must_use]
+  pub fn new() -> Self { Env(None) }
+  #[must_use]
+  pub fn bind(&self, value : T) -> Self { Env(Some(Rc::new((value, self.clone())))) }
+  /// the value of de Bruijn index `i`, if it is bound here.
+  #[must_use]
+  pub fn get(&self, mut i : u32) -> Option<&T> {
+    let mut env = self;
+    loop {
+      let (value, next) = &**env.0.as_ref()?;
+      if i == 0 {
+        return Some(value);
+      }
+      (env, i) = (next, i - 1);
+    }
+  }
+  #[must_use]
+  pub fn len(&self) -> u32 {
+    let (mut env, mut len) = (self, 0);
+    while let Some(bind) = &env.0 {
+      (env, len) = (&bind.1, len + 1);
+    }
+    len
+  }
+  #[must_use]
+  pub fn is_empty(&self) -> bool { self.0.is_none() }
+}
+
+impl<T> Default for Env<T> {
+  fn default() -> Self { Self::new() }
+}
+
+impl<T> Clone for Env<T> {
+  fn clone(&self) -> Self { Env(self.0.clone()) }
+}
+
+impl<T> Drop for Env<T> {
+  // a long chain would otherwise be dropped one recursive call per binding
+  fn drop(&mut self) {
+    let mut next = self.0.take();
+    while let Some(bind) = next {
+      match Rc::try_unwrap(bind) {
+        Ok((_, mut env)) => next = env.0.take(),
+        Err(_) => break,
+      }
+    }
+  }
+}
+
+/// things an environment can bind, which know how to turn back into terms.
+pub trait ReadBack {
+  /// the `Expr` this stands for, placed under `lift` binders.
+  fn read_back(&self, lift : u32) -> Expr;
+}
+
+/// `term` with `env` substituted for its free variables, placed under `lift`
+/// binders. `under` counts the binders of `term` itself already crossed.
+fn read_back_in<T : ReadBack>(term : &Expr, env : &Env<T>, under : u32, lift : u32) -> Expr {
+  match term {
+    Var(i) if *i < under => Var(*i),
+    Var(i) => match env.get(i - under) {
+      Some(value) => value.read_back(lift + under),
+      // free in the whole term
+      None => Var(i - env.len() + lift),
+    },
+    Lam(e) => lam(read_back_in(e, env, under + 1, lift)),
+    App(l, r) => app(
+      read_back_in(l, env, under, lift),
+      read_back_in(r, env, under, lift),
+    ),
+    leaf => leaf.clone(),
+  }
+}
+
+/// a term together with what its free variables are bound to.
+pub struct Closure<'a> {
+  pub term : &'a Expr,
+  pub env : Env<Closure<'a>>,
+}
+
+impl Clone for Closure<'_> {
+  fn clone(&self) -> Self {
+    Closure {
+      term : self.term,
+      env : self.env.clone(),
+    }
+  }
+}
+
+impl ReadBack for Closure<'_> {
+  fn read_back(&self, lift : u32) -> Expr { read_back_in(self.term, &self.env, 0, lift) }
+}
+
+/// Krivine machine: call-by-name. arguments are pushed as closures without
+/// evaluating them, and evaluated each time their variable is reached.
+pub struct Krivine<'a> {
+  /// what is being evaluated
+  pub closure : Closure<'a>,
+  /// the arguments it is applied to, the next one on top
+  pub stack : Vec<Closure<'a>>,
+  /// transitions made so far
+  pub steps : usize,
+}
+
+impl<'a> Krivine<'a> {
+  #[must_use]
+  pub fn new(term : &'a Expr) -> Self {
+    Krivine {
+      closure : Closure {
+        term,
+        env : Env::new(),
+      },
+      stack : Vec::new(),
+      steps : 0,
+    }
+  }
+
+  /// one transition. returns `false` (and does nothing) once the machine has
+  /// stopped: at a lambda with no argument left, or stuck on a free
+  /// variable or a leaf.
+  pub fn step(&mut self) -> bool {
+    let closure = &self.closure;
+    let next = match closure.term {
+      App(l, r) => {
+        self.stack.push(Closure {
+          term : r,
+          env : closure.env.clone(),
+        });
+        Closure {
+          term : l,
+          env : closure.env.clone(),
+        }
+      }
+      Lam(e) => match self.stack.pop() {
+        Some(arg) => Closure {
+          term : e,
+          env : closure.env.bind(arg),
+        },
+        None => return false,
+      },
+      Var(i) => match closure.env.get(*i) {
+        Some(bound) => bound.clone(),
+        None => return false,
+      },
+      _ => return false,
+    };
+    self.closure = next;
+    self.steps += 1;
+    true
+  }
+
+  /// `step` at most `fuel` times. returns whether the machine stopped.
+  pub fn run(&mut self, fuel : usize) -> bool {
+    for _ in 0..fuel {
+      if !self.step() {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// the term the machine is at: the closure applied to the stack.
+  #[must_use]
+  pub fn read_back(&self) -> Expr {
+    let mut ret = self.closure.read_back(0);
+    for arg in self.stack.iter().rev() {
+      ret = app(ret, arg.read_back(0));
+    }
+    ret
+  }
+}
+
+/// what a CEK machine evaluates to.
+pub enum Value<'a> {
+  /// a lambda's body and the environment the lambda was reached in
+  Lam(&'a Expr, Env<Value<'a>>),
+  /// stuck: `head` applied to `args`, the first one first
+  Neutral(Head<'a>, Vec<Value<'a>>),
+}
+
+/// what a neutral value is stuck on.
+#[derive(Clone)]
+pub enum Head<'a> {
+  /// de Bruijn index of a variable free in the whole term
+  Free(u32),
+  /// `Hole`, or anything else that is not a term yet
+  Leaf(&'a Expr),
+}
+
+impl Clone for Value<'_> {
+  fn clone(&self) -> Self {
+    match self {
+      Value::Lam(e, env) => Value::Lam(e, env.clone()),
+      Value::Neutral(head, args) => Value::Neutral(head.clone(), args.clone()),
+    }
+  }
+}
+
+impl ReadBack for Value<'_> {
+  fn read_back(&self, lift : u32) -> Expr {
+    match self {
+      Value::Lam(e, env) => lam(read_back_in(e, env, 1, lift)),
+      Value::Neutral(head, args) => {
+        let mut ret = match head {
+          Head::Free(i) => Var(i + lift),
+          Head::Leaf(leaf) => (*leaf).clone(),
+        };
+        for arg in args {
+          ret = app(ret, arg.read_back(lift));
+        }
+        ret
+      }
+    }
+  }
+}
+
+enum Control<'a> {
+  Eval(&'a Expr, Env<Value<'a>>),
+  Return(Value<'a>),
+}
+
+/// the continuation of a CEK machine, one frame at a time.
+enum Frame<'a> {
+  /// the function is being evaluated, this argument is next
+  Arg(&'a Expr, Env<Value<'a>>),
+  /// the argument is being evaluated, then this gets applied to it
+  Fun(Value<'a>),
+}
+
+/// CEK machine: call-by-value. the function and then the argument of an
+/// application are evaluated before it is contracted, left to right.
+/// applying something stuck stays stuck, with the argument evaluated.
+pub struct Cek<'a> {
+  control : Control<'a>,
+  stack : Vec<Frame<'a>>,
+  /// transitions made so far
+  pub steps : usize,
+}
+
+impl<'a> Cek<'a> {
+  #[must_use]
+  pub fn new(term : &'a Expr) -> Self {
+    Cek {
+      control : Control::Eval(term, Env::new()),
+      stack : Vec::new(),
+      steps : 0,
+    }
+  }
+
+  /// one transition. returns `false` (and does nothing) once the machine has
+  /// stopped with a value and nothing left to do.
+  pub fn step(&mut self) -> bool {
+    let next = match &self.control {
+      Control::Eval(term, env) => match term {
+        App(l, r) => {
+          self.stack.push(Frame::Arg(r, env.clone()));
+          Control::Eval(l, env.clone())
+        }
+        Lam(e) => Control::Return(Value::Lam(e, env.clone())),
+        Var(i) => Control::Return(match env.get(*i) {
+          Some(value) => value.clone(),
+          None => Value::Neutral(Head::Free(i - env.len()), Vec::new()),
+        }),
+        leaf => Control::Return(Value::Neutral(Head::Leaf(leaf), Vec::new())),
+      },
+      Control::Return(value) => match self.stack.pop() {
+        Some(Frame::Arg(r, env)) => {
+          self.stack.push(Frame::Fun(value.clone()));
+          Control::Eval(r, env)
+        }
+        Some(Frame::Fun(Value::Lam(e, env))) => Control::Eval(e, env.bind(value.clone())),
+        Some(Frame::Fun(Value::Neutral(head, mut args))) => {
+          args.push(value.clone());
+          Control::Return(Value::Neutral(head, args))
+        }
+        None => return false,
+      },
+    };
+    self.control = next;
+    self.steps += 1;
+    true
+  }
+
+  /// `step` at most `fuel` times. returns whether the machine stopped.
+  pub fn run(&mut self, fuel : usize) -> bool {
+    for _ in 0..fuel {
+      if !self.step() {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// the value, once stopped.
+  #[must_use]
+  pub fn value(&self) -> Option<&Value<'a>> {
+    match (&self.control, self.stack.is_empty()) {
+      (Control::Return(value), true) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// the term the machine is at: what is in control, plugged into the
+  /// continuation.
+  #[must_use]
+  pub fn read_back(&self) -> Expr {
+    let mut ret = match &self.control {
+      Control::Eval(term, env) => read_back_in(term, env, 0, 0),
+      Control::Return(value) => value.read_back(0),
+    };
+    for frame in self.stack.iter().rev() {
+      ret = match frame {
+        Frame::Arg(r, env) => app(ret, read_back_in(r, env, 0, 0)),
+        Frame::Fun(f) => app(f.read_back(0), ret),
+      };
+    }
+    ret
+  }
+}
@@ -0,0 +1,105 @@
+//! normalization by evaluation. a term is evaluated into `Value`s, where
+//! lambdas are closures and applying them is just evaluating the body with
+//! one more binding; then `quote` reads the value back into an `Expr`,
+//! going under each lambda by applying it to a fresh variable. arguments are
+//! evaluated at most once, and only when needed, so this finds the same
+//! normal forms as `Expr::nf`.
+extern crate alloc;
+use alloc::rc::Rc;
+use core::cell::{Cell, OnceCell};
+use core::prelude::rust_2024::*;
+
+use crate::lambda::{app, lam, Expr};
+use crate::machine::Env;
+use Expr::{App, Lam, Var};
+
+/// what the free variables of a term being evaluated stand for.
+pub type Scope<'a> = Env<Rc<Thunk<'a>>>;
+
+pub enum Value<'a> {
+  /// a lambda's body and the environment the lambda was evaluated in
+  Lam(&'a Expr, Scope<'a>),
+  /// a variable bound while quoting, by de Bruijn level
+  Level(u32),
+  /// de Bruijn index of a variable free in the whole term
+  Free(u32),
+  /// `Hole`, or anything else that is not a term yet
+  Leaf(&'a Expr),
+  /// stuck: the left side is never a `Lam`
+  App(Rc<Value<'a>>, Rc<Thunk<'a>>),
+}
+
+/// an argument, evaluated the first time it is forced.
+pub struct Thunk<'a> {
+  delayed : Cell<Option<(&'a Expr, Scope<'a>)>>,
+  value : OnceCell<Rc<Value<'a>>>,
+}
+
+impl<'a> Thunk<'a> {
+  #[must_use]
+  pub fn new(term : &'a Expr, env : Scope<'a>) -> Self {
+    Thunk {
+      delayed : Cell::new(Some((term, env))),
+      value : OnceCell::new(),
+    }
+  }
+  #[must_use]
+  pub fn ready(value : Rc<Value<'a>>) -> Self {
+    Thunk {
+      delayed : Cell::new(None),
+      value : OnceCell::from(value),
+    }
+  }
+  pub fn force(&self) -> Rc<Value<'a>> {
+    self
+      .value
+      .get_or_init(|| {
+        let (term, env) = self.delayed.take().unwrap();
+        eval(term, &env)
+      })
+      .clone()
+  }
+}
+
+pub fn eval<'a>(term : &'a Expr, env : &Scope<'a>) -> Rc<Value<'a>> {
+  match term {
+    Var(i) => match env.get(*i) {
+      Some(thunk) => thunk.force(),
+      None => Rc::new(Value::Free(i - env.len())),
+    },
+    Lam(e) => Rc::new(Value::Lam(e, env.clone())),
+    App(l, r) => apply(eval(l, env), Rc::new(Thunk::new(r, env.clone()))),
+    leaf => Rc::new(Value::Leaf(leaf)),
+  }
+}
+
+pub fn apply<'a>(f : Rc<Value<'a>>, arg : Rc<Thunk<'a>>) -> Rc<Value<'a>> {
+  match &*f {
+    Value::Lam(e, env) => eval(e, &env.bind(arg)),
+    _ => Rc::new(Value::App(f, arg)),
+  }
+}
+
+/// read `value` back into a normal form, under `depth` binders.
+#[must_use]
+pub fn quote(value : &Value, depth : u32) -> Expr {
+  match value {
+    Value::Lam(e, env) => {
+      let var = Rc::new(Thunk::ready(Rc::new(Value::Level(depth))));
+      lam(quote(&eval(e, &env.bind(var)), depth + 1))
+    }
+    Value::Level(l) => Var(depth - 1 - l),
+    Value::Free(i) => Var(i + depth),
+    Value::Leaf(leaf) => (*leaf).clone(),
+    Value::App(f, arg) => app(quote(f, depth), quote(&arg.force(), depth)),
+  }
+}
+
+/// the normal form of `term`. diverges exactly when `term` has none.
+#[must_use]
+pub fn normalize(term : &Expr) -> Expr { quote(&eval(term, &Env::new()), 0) }
+
+impl Expr {
+  /// same result as `nf`, without substituting.
+  pub fn nf_nbe(&mut self) { *self = normalize(self) }
+}
@@ -0,0 +1,288 @@
+//! reading terms from text. binders are `λ` or `\`, either with names
+//! (`λx y. x y`) or without (`λ λ 1 0`), and application is juxtaposition,
+//! with a lambda's body reaching as far right as it can.
+//!
+//! a variable is a name of an enclosing binder, an index in brackets (`[12]`)
+//! or a circled digit the way `DisplayStruct` prints them. a plain number is
+//! a de Bruijn index too if it is under a binder without a name and smaller
+//! than the number of binders around it, otherwise it is a church numeral.
+//! `DisplayStruct` prints numerals as plain numbers wherever they are, so its
+//! output is read back with `parse_printed`, which takes every plain number
+//! for a numeral. also read are `▪` for a hole and the names `DisplayStruct`
+//! gives constants: `I`, `K`, `S`, `B`, `C`, `W`, `SUCC`, `+`, `*` and `^`,
+//! unless a binder by that name is in the way.
+extern crate alloc;
+use alloc::vec::Vec;
+use core::prelude::rust_2024::*;
+use core::str::FromStr;
+use core::{fmt, write};
+
+use crate::lambda::{
+  app, lam, Expr, CONST, FORK, ID, PLUS, POWER, SUCC, TIMES, VAR_LEAF, VAR_NUMERALS,
+};
+use crate::ski::{COMPOSE, DUP, FLIP};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+  /// not part of any token
+  Char(char),
+  /// a name that is neither bound nor a constant
+  Unbound,
+  /// a number past `u32`
+  TooLarge,
+  Expected(&'static str),
+}
+
+/// what went wrong, and where: `at` counts chars, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+  pub at : usize,
+  pub kind : ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    match self.kind {
+      ParseErrorKind::Char(c) => write!(f, "unexpected {c:?}"),
+      ParseErrorKind::Unbound => write!(f, "unbound name"),
+      ParseErrorKind::TooLarge => write!(f, "number too large"),
+      ParseErrorKind::Expected(what) => write!(f, "expected {what}"),
+    }?;
+    write!(f, " at {}", self.at)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+  Open,
+  Close,
+  Lambda,
+  Dot,
+  Hole,
+  Name(&'a str),
+  Number(u32),
+  Index(u32),
+}
+
+fn number(digits : &str, at : usize) -> Result<u32, ParseError> {
+  digits.parse().map_err(|_| ParseError {
+    at,
+    kind : ParseErrorKind::TooLarge,
+  })
+}
+
+/// the tokens of `input`, each with the char offset it starts at.
+fn lex(input : &str) -> Result<Vec<(Token<'_>, usize)>, ParseError> {
+  let mut tokens = Vec::new();
+  let mut chars = input.char_indices().enumerate().peekable();
+  while let Some((at, (start, c))) = chars.next() {
+    // the byte offset past the run of chars matching `f` after this one
+    let mut run = |f : fn(char) -> bool| {
+      let mut end = start + c.len_utf8();
+      while let Some(&(_, (i, c))) = chars.peek() {
+        if !f(c) {
+          break;
+        }
+        end = i + c.len_utf8();
+        chars.next();
+      }
+      end
+    };
+    let token = match c {
+      _ if c.is_whitespace() => continue,
+      '(' => Token::Open,
+      ')' => Token::Close,
+      'λ' | '\\' => Token::Lambda,
+      '.' => Token::Dot,
+      '▪' => Token::Hole,
+      '+' | '*' | '^' => Token::Name(&input[start..start + 1]),
+      '0'..='9' => Token::Number(number(&input[start..run(|c| c.is_ascii_digit())], at)?),
+      '[' => {
+        let end = run(|c| c.is_ascii_digit());
+        if end == start + 1 {
+          return Err(ParseError {
+            at : at + 1,
+            kind : ParseErrorKind::Expected("an index"),
+          });
+        }
+        let index = number(&input[start + 1..end], at)?;
+        match chars.next() {
+          Some((_, (_, ']'))) => Token::Index(index),
+          Some((at, (_, c))) => {
+            return Err(ParseError {
+              at,
+              kind : ParseErrorKind::Char(c),
+            })
+          }
+          None => {
+            return Err(ParseError {
+              at : input.chars().count(),
+              kind : ParseErrorKind::Expected("]"),
+            })
+          }
+        }
+      }
+      _ if c.is_ascii_alphabetic() || c == '_' => {
+        let end = run(|c| c.is_ascii_alphanumeric() || c == '_' || c == '\'');
+        Token::Name(&input[start..end])
+      }
+      _ => match VAR_NUMERALS.iter().chain(&VAR_LEAF).position(|&v| v == c) {
+        Some(i) => Token::Index((i % VAR_NUMERALS.len()) as u32),
+        None => {
+          return Err(ParseError {
+            at,
+            kind : ParseErrorKind::Char(c),
+          })
+        }
+      },
+    };
+    tokens.push((token, at));
+  }
+  Ok(tokens)
+}
+
+struct Parser<'a> {
+  tokens : Vec<(Token<'a>, usize)>,
+  pos : usize,
+  /// the char count of the input, where running out of tokens is reported
+  end : usize,
+  /// the binders around the current token, innermost last
+  scope : Vec<Option<&'a str>>,
+  /// whether plain numbers are always numerals, as in printed output
+  numerals : bool,
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&self) -> Option<Token<'a>> { self.tokens.get(self.pos).map(|&(t, _)| t) }
+  fn at(&self) -> usize { self.tokens.get(self.pos).map_or(self.end, |&(_, at)| at) }
+  fn error(&self, kind : ParseErrorKind) -> ParseError {
+    ParseError {
+      at : self.at(),
+      kind,
+    }
+  }
+
+  /// applications, maybe ending in a lambda
+  fn term(&mut self) -> Result<Expr, ParseError> {
+    let mut ret = None;
+    loop {
+      let next = match self.peek() {
+        None | Some(Token::Close) => break,
+        Some(Token::Lambda) => self.lambda()?,
+        _ => self.atom()?,
+      };
+      ret = Some(match ret {
+        Some(f) => app(f, next),
+        None => next,
+      });
+    }
+    ret.ok_or_else(|| self.error(ParseErrorKind::Expected("a term")))
+  }
+
+  fn lambda(&mut self) -> Result<Expr, ParseError> {
+    self.pos += 1;
+    let names = self.tokens[self.pos..]
+      .iter()
+      .take_while(|(t, _)| matches!(t, Token::Name(_)))
+      .count();
+    let depth = self.scope.len();
+    if let Some(&(Token::Dot, _)) = self.tokens.get(self.pos + names) {
+      for &(t, _) in &self.tokens[self.pos..self.pos + names] {
+        let Token::Name(name) = t else { unreachable!() };
+        self.scope.push(Some(name));
+      }
+      self.pos += names + 1;
+    }
+    if self.scope.len() == depth {
+      self.scope.push(None);
+    }
+    let mut ret = self.term()?;
+    for _ in depth..self.scope.len() {
+      ret = lam(ret);
+    }
+    self.scope.truncate(depth);
+    Ok(ret)
+  }
+
+  fn atom(&mut self) -> Result<Expr, ParseError> {
+    let at = self.at();
+    let token = self
+      .peek()
+      .ok_or_else(|| self.error(ParseErrorKind::Expected("a term")))?;
+    self.pos += 1;
+    Ok(match token {
+      Token::Open => {
+        let ret = self.term()?;
+        if self.peek() != Some(Token::Close) {
+          return Err(self.error(ParseErrorKind::Expected(")")));
+        }
+        self.pos += 1;
+        ret
+      }
+      Token::Hole => Expr::Hole,
+      Token::Index(i) => Expr::Var(i),
+      Token::Number(n) => {
+        let nameless = self.scope.contains(&None);
+        if !self.numerals && nameless && (n as usize) < self.scope.len() {
+          Expr::Var(n)
+        } else {
+          Expr::from_nat(n)
+        }
+      }
+      Token::Name(name) => match self.scope.iter().rev().position(|&b| b == Some(name)) {
+        Some(i) => Expr::Var(i as u32),
+        None => match name {
+          "I" => ID.clone(),
+          "K" => CONST.clone(),
+          "S" => FORK.clone(),
+          "B" => COMPOSE.clone(),
+          "C" => FLIP.clone(),
+          "W" => DUP.clone(),
+          "SUCC" => SUCC.clone(),
+          "+" => PLUS.clone(),
+          "*" => TIMES.clone(),
+          "^" => POWER.clone(),
+          _ => {
+            return Err(ParseError {
+              at,
+              kind : ParseErrorKind::Unbound,
+            })
+          }
+        },
+      },
+      Token::Close | Token::Dot | Token::Lambda => {
+        return Err(ParseError {
+          at,
+          kind : ParseErrorKind::Expected("a term"),
+        })
+      }
+    })
+  }
+}
+
+fn parse_with(input : &str, numerals : bool) -> Result<Expr, ParseError> {
+  let mut parser = Parser {
+    tokens : lex(input)?,
+    pos : 0,
+    end : input.chars().count(),
+    scope : Vec::new(),
+    numerals,
+  };
+  let ret = parser.term()?;
+  if parser.peek().is_some() {
+    return Err(parser.error(ParseErrorKind::Expected("end of input")));
+  }
+  Ok(ret)
+}
+
+/// read a term written as described at the top of this module.
+pub fn parse(input : &str) -> Result<Expr, ParseError> { parse_with(input, false) }
+
+/// read back what `DisplayStruct` printed: like `parse`, but a plain number
+/// is a church numeral even under a binder without a name.
+pub fn parse_printed(input : &str) -> Result<Expr, ParseError> { parse_with(input, true) }
+
+impl FromStr for Expr {
+  type Err = ParseError;
+  fn from_str(s : &str) -> Result<Expr, ParseError> { parse(s) }
+}
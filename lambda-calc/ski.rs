@@ -0,0 +1,456 @@
+//! combinators: bracket abstraction from `Expr`, graph reduction in a `Heap`
+//! and the way back.
+//!
+//! `[x]` takes a lambda apart as
+//!
+//! - `[x] x = I`
+//! - `[x] e = K e` when `x` is not in `e`
+//! - `[x] (f g) = S ([x] f) ([x] g)`
+//!
+//! and with `Basis::Bcw` first tries
+//!
+//! - `[x] (f g) = B f ([x] g)` when `x` is not in `f`
+//! - `[x] (f g) = C ([x] f) g` when `x` is not in `g`
+//! - `[x] (f x) = W ([x] f)`
+//!
+//! which keeps the result from growing quite as fast.
+extern crate alloc;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::prelude::rust_2024::*;
+use core::{fmt, write};
+use lambda_arena::heap::{Heap, HeapError, Idx, Object, Root};
+use lambda_arena::lambda::U;
+use once_cell::sync::Lazy;
+
+use crate::lambda::{app, lam, ArenaError, Expr, CONST, FORK, ID, VAR_NUMERALS};
+use Expr::{Hole, Var};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comb {
+  /// `S f g x = f x (g x)`
+  S,
+  /// `K x y = x`
+  K,
+  /// `I x = x`
+  I,
+  /// `B f g x = f (g x)`
+  B,
+  /// `C f x y = f y x`
+  C,
+  /// `W f x = f x x`
+  W,
+}
+
+impl Comb {
+  const ALL : [Comb; 6] = [Comb::S, Comb::K, Comb::I, Comb::B, Comb::C, Comb::W];
+
+  /// how many arguments it takes to contract
+  #[must_use]
+  pub fn arity(self) -> usize {
+    match self {
+      Comb::I => 1,
+      Comb::K | Comb::W => 2,
+      Comb::S | Comb::B | Comb::C => 3,
+    }
+  }
+
+  /// the closed lambda term it stands for
+  #[must_use]
+  pub fn expr(self) -> Expr {
+    match self {
+      Comb::S => FORK.clone(),
+      Comb::K => CONST.clone(),
+      Comb::I => ID.clone(),
+      Comb::B => COMPOSE.clone(),
+      Comb::C => FLIP.clone(),
+      Comb::W => DUP.clone(),
+    }
+  }
+}
+
+pub static COMPOSE : Lazy<Expr> = Lazy::new(|| lam(lam(lam(app(Var(2), app(Var(1), Var(0)))))));
+pub static FLIP : Lazy<Expr> = Lazy::new(|| lam(lam(lam(app(app(Var(2), Var(0)), Var(1))))));
+pub static DUP : Lazy<Expr> = Lazy::new(|| lam(lam(app(app(Var(1), Var(0)), Var(0)))));
+
+/// which combinators bracket abstraction may use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Basis {
+  /// `S`, `K` and `I` only
+  Ski,
+  /// `B`, `C` and `W` where they do
+  Bcw,
+}
+
+/// a combinator term. variables are de Bruijn indices past every binder,
+/// there are none left inside.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ski {
+  Comb(Comb),
+  Var(u32),
+  Hole,
+  App(Box<Ski>, Box<Ski>),
+}
+
+fn ap(l : Ski, r : Ski) -> Ski { Ski::App(Box::new(l), Box::new(r)) }
+
+impl Ski {
+  pub fn from_expr(expr : &Expr, basis : Basis) -> Result<Ski, ArenaError> {
+    Ok(match expr {
+      Var(u) => Ski::Var(*u),
+      Expr::Lam(e) => Ski::from_expr(e, basis)?.abstract_(basis),
+      Expr::App(l, r) => ap(Ski::from_expr(l, basis)?, Ski::from_expr(r, basis)?),
+      Hole => Ski::Hole,
+      Expr::Slot => return Err(ArenaError::Slot),
+      Expr::Thunk(_) => return Err(ArenaError::Thunk),
+    })
+  }
+
+  /// the lambda term, with each combinator spelled out. beta equal to what
+  /// `from_expr` got, but only `nf` makes it the same again.
+  #[must_use]
+  pub fn to_expr(&self) -> Expr {
+    match self {
+      Ski::Comb(c) => c.expr(),
+      Ski::Var(u) => Var(*u),
+      Ski::Hole => Hole,
+      Ski::App(l, r) => app(l.to_expr(), r.to_expr()),
+    }
+  }
+
+  fn uses(&self, v : u32) -> bool {
+    match self {
+      Ski::Var(u) => *u == v,
+      Ski::App(l, r) => l.uses(v) || r.uses(v),
+      _ => false,
+    }
+  }
+
+  /// one binder less, for terms that do not use index 0
+  fn unshift(self) -> Ski {
+    match self {
+      Ski::Var(u) => Ski::Var(u - 1),
+      Ski::App(l, r) => ap(l.unshift(), r.unshift()),
+      c => c,
+    }
+  }
+
+  /// `[x] self` for `x` de Bruijn index 0
+  fn abstract_(self, basis : Basis) -> Ski {
+    use Comb::{B, C, I, K, S, W};
+    if !self.uses(0) {
+      return ap(Ski::Comb(K), self.unshift());
+    }
+    let Ski::App(f, g) = self else {
+      return Ski::Comb(I);
+    };
+    let g_is_x = *g == Ski::Var(0);
+    match (basis, f.uses(0), g.uses(0)) {
+      (Basis::Bcw, false, _) => ap(ap(Ski::Comb(B), f.unshift()), g.abstract_(basis)),
+      (Basis::Bcw, _, false) => ap(ap(Ski::Comb(C), f.abstract_(basis)), g.unshift()),
+      (Basis::Bcw, ..) if g_is_x => ap(Ski::Comb(W), f.abstract_(basis)),
+      _ => ap(ap(Ski::Comb(S), f.abstract_(basis)), g.abstract_(basis)),
+    }
+  }
+}
+
+impl fmt::Display for Ski {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Ski::Comb(c) => write!(f, "{c:?}"),
+      Ski::Var(u) if *u <= 10 => write!(f, "{}", VAR_NUMERALS[*u as usize]),
+      Ski::Var(u) => write!(f, "[{u}]"),
+      Ski::Hole => write!(f, "▪"),
+      Ski::App(l, r) => match &**r {
+        Ski::App(..) => write!(f, "{l} ({r})"),
+        _ => write!(f, "{l} {r}"),
+      },
+    }
+  }
+}
+
+/// a `Ski` node in a `Heap`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Node(U, U);
+
+#[derive(Debug, Default)]
+pub enum NodeRepr {
+  #[default]
+  Hole,
+  Comb(Comb),
+  Var(U),
+  App(Idx<Node>, Idx<Node>),
+  /// stands for the node it points to, what `I` and `K` contract to
+  Ind(Idx<Node>),
+}
+
+impl From<NodeRepr> for Node {
+  fn from(value : NodeRepr) -> Self {
+    match value {
+      NodeRepr::Hole => Node(0, 0),
+      NodeRepr::Comb(c) => Node(0, c as U + 1),
+      // neither is ever an index, see `U`
+      NodeRepr::Var(u) => Node(U::MAX, u),
+      NodeRepr::Ind(to) => Node(to.raw, U::MAX),
+      NodeRepr::App(l, r) => Node(l.raw, r.raw),
+    }
+  }
+}
+
+impl From<Node> for NodeRepr {
+  fn from(value : Node) -> Self {
+    match value {
+      Node(0, 0) => NodeRepr::Hole,
+      Node(0, c) => NodeRepr::Comb(Comb::ALL[c as usize - 1]),
+      Node(U::MAX, u) => NodeRepr::Var(u),
+      Node(to, U::MAX) => NodeRepr::Ind(to.into()),
+      Node(l, r) => NodeRepr::App(l.into(), r.into()),
+    }
+  }
+}
+
+impl Object for Node {
+  fn visit_refs(&self, f : &mut dyn FnMut(Idx<Self>)) {
+    match NodeRepr::from(*self) {
+      NodeRepr::App(l, r) => {
+        f(l);
+        f(r);
+      }
+      NodeRepr::Ind(to) => f(to),
+      _ => {}
+    }
+  }
+  fn map_refs(&mut self, f : &mut dyn FnMut(Idx<Self>) -> Idx<Self>) {
+    *self = match NodeRepr::from(*self) {
+      NodeRepr::App(l, r) => NodeRepr::App(f(l), f(r)),
+      NodeRepr::Ind(to) => NodeRepr::Ind(f(to)),
+      n => n,
+    }
+    .into();
+  }
+  fn moved(to : Idx<Self>) -> Self { NodeRepr::Ind(to).into() }
+  fn moved_to(&self) -> Idx<Self> { self.0.into() }
+}
+
+impl fmt::Debug for Node {
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", NodeRepr::from(*self))
+  }
+}
+
+/// a graph being reduced, and what is left of the fuel for it
+struct Reduce<'a, const PAGESIZE: usize, const G1PAGES: usize> {
+  heap : &'a mut Heap<Node, PAGESIZE, G1PAGES>,
+  fuel : usize,
+}
+
+impl<const PAGESIZE: usize, const G1PAGES: usize> Reduce<'_, PAGESIZE, G1PAGES> {
+  fn repr(&self, at : Idx<Node>) -> Result<NodeRepr, HeapError> {
+    Ok(NodeRepr::from(*self.heap.get(at)?))
+  }
+  fn follow(&self, mut at : Idx<Node>) -> Result<Idx<Node>, HeapError> {
+    while let NodeRepr::Ind(to) = self.repr(at)? {
+      at = to;
+    }
+    Ok(at)
+  }
+  fn right(&self, at : Idx<Node>) -> Result<Idx<Node>, HeapError> {
+    let NodeRepr::App(_, r) = self.repr(at)? else {
+      unreachable!()
+    };
+    Ok(r)
+  }
+
+  /// contract at the head of `at` until it is not a redex, unwinding the
+  /// spine on the root stack. `Ok(false)` if the fuel ran out first.
+  fn whnf(&mut self, at : Idx<Node>) -> Result<bool, HeapError> {
+    let head = self.heap.root(at)?;
+    let spine = self.heap.root_mark();
+    let mut len = 0;
+    let ret = loop {
+      let at = self.follow(self.heap.rooted(head))?;
+      let comb = match self.repr(at)? {
+        NodeRepr::App(l, _) => {
+          self.heap.push_frame(at, 0)?;
+          self.heap.set_root(head, l);
+          len += 1;
+          continue;
+        }
+        NodeRepr::Comb(comb) if comb.arity() <= len => comb,
+        _ => break true,
+      };
+      if self.fuel == 0 {
+        break false;
+      }
+      self.fuel -= 1;
+      // the applications holding the arguments, the innermost first. the
+      // last one is the redex, it gets overwritten with the result.
+      let mut apps = [0.into(); 3];
+      for app in apps.iter_mut().take(comb.arity()) {
+        *app = self.heap.pop_frame(spine).unwrap().0;
+      }
+      len -= comb.arity();
+      let redex = apps[comb.arity() - 1];
+      let mark = self.heap.root_mark();
+      let mut args = [mark; 3];
+      for (arg, &app) in args.iter_mut().zip(&apps[..comb.arity()]) {
+        *arg = self.heap.root(self.right(app)?)?;
+      }
+      let redex = self.heap.root(redex)?;
+      let arg = |heap : &Heap<Node, PAGESIZE, G1PAGES>, i : usize| heap.rooted(args[i]);
+      let result = match comb {
+        Comb::I | Comb::K => NodeRepr::Ind(arg(self.heap, 0)),
+        Comb::S => {
+          let fx = self
+            .heap
+            .init(NodeRepr::App(arg(self.heap, 0), arg(self.heap, 2)).into())?;
+          let fx = self.heap.root(fx)?;
+          let gx = self
+            .heap
+            .init(NodeRepr::App(arg(self.heap, 1), arg(self.heap, 2)).into())?;
+          NodeRepr::App(self.heap.rooted(fx), gx)
+        }
+        Comb::B => {
+          let gx = self
+            .heap
+            .init(NodeRepr::App(arg(self.heap, 1), arg(self.heap, 2)).into())?;
+          NodeRepr::App(arg(self.heap, 0), gx)
+        }
+        Comb::C => {
+          let fy = self
+            .heap
+            .init(NodeRepr::App(arg(self.heap, 0), arg(self.heap, 2)).into())?;
+          NodeRepr::App(fy, arg(self.heap, 1))
+        }
+        Comb::W => {
+          let fx = self
+            .heap
+            .init(NodeRepr::App(arg(self.heap, 0), arg(self.heap, 1)).into())?;
+          NodeRepr::App(fx, arg(self.heap, 1))
+        }
+      };
+      let redex = self.heap.rooted(redex);
+      self.heap.set(redex, result.into())?;
+      self.heap.unroot(mark);
+      self.heap.set_root(head, redex);
+    };
+    self.heap.unroot(head);
+    Ok(ret)
+  }
+
+  /// `whnf` everywhere, arguments after their function. the nodes still to
+  /// do wait on the root stack.
+  fn nf(&mut self, at : Idx<Node>) -> Result<bool, HeapError> {
+    let base = self.heap.root_mark();
+    self.heap.push_frame(at, 0)?;
+    let ret = loop {
+      let Some((at, _)) = self.heap.pop_frame(base) else {
+        break true;
+      };
+      let top = self.heap.root(at)?;
+      if !self.whnf(at)? {
+        break false;
+      }
+      let mut at = self.follow(self.heap.rooted(top))?;
+      self.heap.unroot(top);
+      while let NodeRepr::App(l, r) = self.repr(at)? {
+        self.heap.push_frame(r, 0)?;
+        at = self.follow(l)?;
+      }
+    };
+    self.heap.unroot(base);
+    Ok(ret)
+  }
+}
+
+impl Ski {
+  /// copy into `heap`, node for node. the result is not rooted.
+  pub fn to_arena<const PAGESIZE: usize, const G1PAGES: usize>(
+    &self,
+    heap : &mut Heap<Node, PAGESIZE, G1PAGES>,
+  ) -> Result<Idx<Node>, ArenaError> {
+    let base = heap.root_mark();
+    let ret = self.to_arena_(heap, base);
+    heap.unroot(base);
+    ret
+  }
+  fn to_arena_<const PAGESIZE: usize, const G1PAGES: usize>(
+    &self,
+    heap : &mut Heap<Node, PAGESIZE, G1PAGES>,
+    base : Root,
+  ) -> Result<Idx<Node>, ArenaError> {
+    // the same walk as `Expr::to_arena`
+    let mut todo = vec![(self, false)];
+    let child =
+      |heap : &mut Heap<Node, PAGESIZE, G1PAGES>| heap.pop_frame(base).expect("copied subterm").0;
+    while let Some((e, children)) = todo.pop() {
+      let repr = match (e, children) {
+        (Ski::App(l, r), false) => {
+          todo.extend([(e, true), (r, false), (l, false)]);
+          continue;
+        }
+        (Ski::App(..), true) => {
+          let r = child(heap);
+          NodeRepr::App(child(heap), r)
+        }
+        (Ski::Comb(c), _) => NodeRepr::Comb(*c),
+        (Ski::Var(u), _) => match U::try_from(*u) {
+          Ok(u) if u != U::MAX => NodeRepr::Var(u),
+          _ => return Err(ArenaError::VarTooLarge(*u)),
+        },
+        (Ski::Hole, _) => NodeRepr::Hole,
+      };
+      let at = heap.init(repr.into())?;
+      heap.push_frame(at, 0)?;
+    }
+    Ok(child(heap))
+  }
+
+  pub fn from_arena<const PAGESIZE: usize, const G1PAGES: usize>(
+    heap : &Heap<Node, PAGESIZE, G1PAGES>,
+    at : Idx<Node>,
+  ) -> Result<Ski, HeapError> {
+    let mut todo = vec![(at, false)];
+    let mut done = Vec::new();
+    while let Some((at, children)) = todo.pop() {
+      let e = match (NodeRepr::from(*heap.get(at)?), children) {
+        (NodeRepr::App(l, r), false) => {
+          todo.extend([(at, true), (r, false), (l, false)]);
+          continue;
+        }
+        (NodeRepr::Ind(to), _) => {
+          todo.push((to, false));
+          continue;
+        }
+        (NodeRepr::App(..), true) => {
+          let r = done.pop().unwrap();
+          ap(done.pop().unwrap(), r)
+        }
+        (NodeRepr::Hole, _) => Ski::Hole,
+        (NodeRepr::Comb(c), _) => Ski::Comb(c),
+        (NodeRepr::Var(u), _) => Ski::Var(u.into()),
+      };
+      done.push(e);
+    }
+    Ok(done.pop().unwrap())
+  }
+
+  /// reduce to normal form by graph reduction in `heap`, contracting at most
+  /// `fuel` times. shared arguments of `S` and `W` get reduced once. returns
+  /// whether that reached the normal form, `self` is the last state either
+  /// way. `heap` is left with garbage only, nothing stays rooted.
+  pub fn nf_in<const PAGESIZE: usize, const G1PAGES: usize>(
+    &mut self,
+    heap : &mut Heap<Node, PAGESIZE, G1PAGES>,
+    fuel : usize,
+  ) -> Result<bool, ArenaError> {
+    let at = self.to_arena(heap)?;
+    let top = heap.root(at)?;
+    let mut reduce = Reduce { heap, fuel };
+    let done = reduce.nf(at);
+    let at = reduce.heap.rooted(top);
+    reduce.heap.unroot(top);
+    let done = done?;
+    *self = Ski::from_arena(reduce.heap, at)?;
+    Ok(done)
+  }
+}
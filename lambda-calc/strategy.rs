@@ -0,0 +1,213 @@
+//! one contraction at a time, by whichever strategy picks the redex. the
+//! strategies differ in which redex goes first, and in which they leave
+//! alone for good: none of the weak ones look under a binder.
+extern crate alloc;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::prelude::rust_2024::*;
+use core::{fmt, write};
+use lambda_arena::heap::Fnv;
+
+use crate::lambda::Expr;
+use Expr::{App, Lam};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+  /// leftmost outermost, everywhere. what `Expr::nf` does
+  NormalOrder,
+  /// leftmost innermost, everywhere: function and argument are normal
+  /// before a redex is contracted
+  ApplicativeOrder,
+  /// leftmost innermost, never under a binder
+  CallByValue,
+  /// leftmost outermost, never under a binder. unlike `WeakHead` it goes on
+  /// into the arguments once the head is stuck
+  CallByName,
+  /// the redex at the head, under binders as well
+  Head,
+  /// the redex at the head, while the term is not a lambda. what `Expr::hnf`
+  /// does
+  WeakHead,
+}
+
+impl Strategy {
+  pub const ALL : [Strategy; 6] = [
+    Strategy::NormalOrder,
+    Strategy::ApplicativeOrder,
+    Strategy::CallByValue,
+    Strategy::CallByName,
+    Strategy::Head,
+    Strategy::WeakHead,
+  ];
+
+  fn under_binders(self) -> bool {
+    matches!(
+      self,
+      Strategy::NormalOrder | Strategy::ApplicativeOrder | Strategy::Head
+    )
+  }
+  fn into_arguments(self) -> bool { !matches!(self, Strategy::Head | Strategy::WeakHead) }
+  fn innermost(self) -> bool { matches!(self, Strategy::ApplicativeOrder | Strategy::CallByValue) }
+}
+
+impl fmt::Display for Strategy {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      Strategy::NormalOrder => "normal",
+      Strategy::ApplicativeOrder => "applicative",
+      Strategy::CallByValue => "cbv",
+      Strategy::CallByName => "cbn",
+      Strategy::Head => "head",
+      Strategy::WeakHead => "whnf",
+    };
+    write!(f, "{name}")
+  }
+}
+
+/// a step from a term to one of its subterms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dir {
+  Body,
+  Left,
+  Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepResult {
+  /// contracted the redex this path leads to from the top
+  Contracted(Vec<Dir>),
+  /// nothing left the strategy would contract
+  Done,
+}
+
+/// pushes the path to the redex `strategy` picks in `expr` onto `path`.
+/// `false`, with `path` as it was, if there is none.
+fn find(expr : &Expr, strategy : Strategy, path : &mut Vec<Dir>) -> bool {
+  let mut descend = |expr : &Expr, dir| {
+    path.push(dir);
+    let found = find(expr, strategy, path);
+    if !found {
+      path.pop();
+    }
+    found
+  };
+  match expr {
+    App(box Lam(_), _) if !strategy.innermost() => true,
+    Lam(e) => strategy.under_binders() && descend(e, Dir::Body),
+    App(l, r) => {
+      descend(l, Dir::Left)
+        || strategy.into_arguments() && descend(r, Dir::Right)
+        || matches!(**l, Lam(_))
+    }
+    _ => false,
+  }
+}
+
+impl Expr {
+  /// the subterm `path` leads to
+  pub fn at_path(&mut self, path : &[Dir]) -> Option<&mut Expr> {
+    let mut at = self;
+    for dir in path {
+      at = match (at, dir) {
+        (Lam(e), Dir::Body) | (App(e, _), Dir::Left) | (App(_, e), Dir::Right) => e,
+        _ => return None,
+      };
+    }
+    Some(at)
+  }
+
+  /// the path to the redex `strategy` would contract next.
+  pub fn redex_path(&self, strategy : Strategy) -> Option<Vec<Dir>> {
+    let mut path = Vec::new();
+    find(self, strategy, &mut path).then_some(path)
+  }
+
+  /// contract the redex `strategy` picks, if there is one.
+  pub fn step(&mut self, strategy : Strategy) -> StepResult {
+    let Some(path) = self.redex_path(strategy) else {
+      return StepResult::Done;
+    };
+    self.at_path(&path).unwrap().beta();
+    StepResult::Contracted(path)
+  }
+}
+
+/// how far `Expr::reduce` may go. `None` is no limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+  pub steps : Option<usize>,
+  /// the most nodes the term may have, counted by `Expr::size`
+  pub nodes : Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+  /// nothing left to contract, after this many steps
+  Normal(usize),
+  OutOfFuel,
+  /// the term grew past `Budget::nodes`
+  OutOfMemory,
+  /// after `at` steps the term was the same as `period` steps before, so it
+  /// would go round forever
+  Looping {
+    at : usize,
+    period : usize,
+  },
+}
+
+/// only used to tell terms apart before comparing them.
+fn hash(expr : &Expr) -> u64 {
+  let mut hasher = Fnv::default();
+  expr.hash(&mut hasher);
+  hasher.finish()
+}
+
+impl Expr {
+  /// the number of nodes in the term
+  pub fn size(&self) -> usize {
+    match self {
+      Lam(e) => 1 + e.size(),
+      App(l, r) => 1 + l.size() + r.size(),
+      _ => 1,
+    }
+  }
+
+  /// step with `strategy` until done or out of `budget`. repeats are found
+  /// the way Brent does: one earlier term is kept, and replaced whenever the
+  /// steps since it reach a power of two, so a loop of any period is caught
+  /// for the price of a copy of the term and a hash per step.
+  pub fn reduce(&mut self, strategy : Strategy, budget : Budget) -> Outcome {
+    let mut seen = self.clone();
+    let mut seen_hash = hash(self);
+    let (mut since, mut power) = (0, 1);
+    for at in 0.. {
+      // a term that is normal after exactly the last step it may take is
+      // still normal, so look for a redex before counting fuel
+      let Some(path) = self.redex_path(strategy) else {
+        return Outcome::Normal(at);
+      };
+      if budget.steps.is_some_and(|steps| at >= steps) {
+        return Outcome::OutOfFuel;
+      }
+      self.at_path(&path).unwrap().beta();
+      if budget.nodes.is_some_and(|nodes| self.size() > nodes) {
+        return Outcome::OutOfMemory;
+      }
+      since += 1;
+      let h = hash(self);
+      if h == seen_hash && *self == seen {
+        return Outcome::Looping {
+          at : at + 1,
+          period : since,
+        };
+      }
+      if since == power {
+        seen.clone_from(self);
+        seen_hash = h;
+        since = 0;
+        power *= 2;
+      }
+    }
+    unreachable!()
+  }
+}
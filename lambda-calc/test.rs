@@ -0,0 +1,760 @@
+#[test]
+fn test_closed() {
+  use crate::lambda::*;
+  use Expr::*;
+  assert!(!Var(0).closed(0));
+  assert!(Var(0).closed(1));
+  assert!(lam(Var(0)).closed(0));
+  assert!(!lam(Var(1)).closed(0));
+  // the free `0` has to be shifted to stay free under the binder
+  let mut e = app(lam(lam(Var(1))), Var(0));
+  e.beta();
+  assert_eq!(e, lam(Var(1)));
+}
+
+/// church arithmetic, with the numeral each one comes to
+#[cfg(test)]
+fn arithmetic() -> [(crate::lambda::Expr, u32); 4] {
+  use crate::lambda::*;
+  let n = Expr::from_nat;
+  [
+    (app(SUCC.clone(), n(4)), 5),
+    (app(app(PLUS.clone(), n(2)), n(3)), 5),
+    (app(app(TIMES.clone(), n(2)), n(3)), 6),
+    (app(app(POWER.clone(), n(2)), n(3)), 8),
+  ]
+}
+
+#[test]
+fn test_nf_in() {
+  use crate::lambda::*;
+  use lambda_arena::heap::Heap;
+  use lambda_arena::lambda::Term;
+  let mut heap : Heap<Term, 64, 64> = Heap::new();
+  for (e, n) in arithmetic() {
+    let mut nf = e.clone();
+    nf.nf();
+    assert_eq!(nf.to_nat(), Some(n));
+    let at = e.to_arena(&mut heap).unwrap();
+    assert_eq!(Expr::from_arena(&heap, at), Ok(e.clone()));
+    let at = heap.nf(at).unwrap();
+    assert_eq!(Expr::from_arena(&heap, at), Ok(nf.clone()));
+    let mut e = e;
+    e.nf_in(&mut heap).unwrap();
+    assert_eq!(e, nf);
+  }
+  // the arena stores an index plus one, so `U::MAX` itself does not fit
+  use lambda_arena::lambda::U;
+  let largest = Expr::Var(u32::from(U::MAX) - 1);
+  let at = largest.to_arena(&mut heap).unwrap();
+  assert_eq!(Expr::from_arena(&heap, at), Ok(largest));
+  assert_eq!(
+    lam(Expr::Var(u32::from(U::MAX))).to_arena(&mut heap),
+    Err(ArenaError::VarTooLarge(u32::from(U::MAX)))
+  );
+}
+
+/// runs `f` on a stack far too small to recurse once per node of the terms
+/// below. what it returns is dropped by the caller, as dropping those terms
+/// does recurse.
+#[cfg(test)]
+fn on_small_stack<T : Send + 'static>(f : impl FnOnce() -> T + Send + 'static) -> T {
+  std::thread::Builder::new()
+    .stack_size(64 * 1024)
+    .spawn(f)
+    .unwrap()
+    .join()
+    .unwrap()
+}
+
+#[test]
+fn test_deep_arena() {
+  use crate::lambda::*;
+  use crate::ski::*;
+  use lambda_arena::heap::Heap;
+  use lambda_arena::lambda::Term;
+  use std::boxed::Box;
+  let mut heap : Box<Heap<Term, 4096, 15>> = Box::default();
+  let n = Expr::from_nat(5000);
+  let (n, copy) = on_small_stack(move || {
+    let at = n.to_arena(&mut heap).unwrap();
+    let copy = Expr::from_arena(&heap, at).unwrap();
+    (n, copy)
+  });
+  assert_eq!(copy.to_nat(), Some(5000));
+  drop((n, copy));
+  // K K … K, leaning left
+  let mut nodes : Box<Heap<Node, 4096, 15>> = Box::default();
+  let mut k = Ski::Comb(Comb::K);
+  for _ in 0..5000 {
+    k = Ski::App(Box::new(k), Box::new(Ski::Comb(Comb::K)));
+  }
+  let (k, copy) = on_small_stack(move || {
+    let at = k.to_arena(&mut nodes).unwrap();
+    let copy = Ski::from_arena(&nodes, at).unwrap();
+    (k, copy)
+  });
+  assert_eq!(k, copy);
+}
+
+/// terms built out of `S`, `K` and `I`, some of them open
+#[cfg(test)]
+fn combinators() -> [crate::lambda::Expr; 5] {
+  use crate::lambda::*;
+  use Expr::*;
+  let (s, k, i) = (FORK.clone(), CONST.clone(), ID.clone());
+  [
+    app(app(app(s.clone(), k.clone()), k.clone()), Var(0)),
+    app(app(k.clone(), Var(0)), Var(1)),
+    app(app(s.clone(), k.clone()), i.clone()),
+    app(app(app(s.clone(), i.clone()), i.clone()), Var(2)),
+    app(app(s.clone(), app(k.clone(), app(s, i))), k),
+  ]
+}
+
+#[test]
+fn test_machines() {
+  use crate::machine::*;
+  let cases = arithmetic()
+    .map(|(e, _)| e)
+    .into_iter()
+    .chain(combinators());
+  for e in cases {
+    let mut nf = e.clone();
+    nf.nf();
+    let mut k = Krivine::new(&e);
+    assert!(k.run(10000));
+    let mut whnf = k.read_back();
+    assert!(whnf.head().is_none(), "{e}");
+    whnf.nf();
+    assert_eq!(whnf, nf, "{e}");
+    let mut c = Cek::new(&e);
+    assert!(c.run(10000));
+    assert!(c.value().is_some());
+    let mut whnf = c.read_back();
+    assert!(whnf.head().is_none(), "{e}");
+    whnf.nf();
+    assert_eq!(whnf, nf, "{e}");
+  }
+  // stopped halfway, they still read back as the same term
+  let (e, n) = &arithmetic()[3];
+  let mut k = Krivine::new(e);
+  assert!(!k.run(3));
+  let mut mid = k.read_back();
+  mid.nf();
+  assert_eq!(mid.to_nat(), Some(*n));
+  let mut c = Cek::new(e);
+  assert!(!c.run(5));
+  let mut mid = c.read_back();
+  mid.nf();
+  assert_eq!(mid.to_nat(), Some(*n));
+}
+
+#[test]
+fn test_machine_orders() {
+  use crate::lambda::*;
+  use crate::machine::*;
+  use Expr::*;
+  // K I Ω: by name Ω is dropped unevaluated, by value it is evaluated first
+  let w = lam(app(Var(0), Var(0)));
+  let kio = app(app(CONST.clone(), ID.clone()), app(w.clone(), w));
+  let mut k = Krivine::new(&kio);
+  assert!(k.run(100));
+  assert_eq!(k.read_back(), *ID);
+  let mut c = Cek::new(&kio);
+  assert!(!c.run(1000));
+  // stuck on a free variable, by value the argument still gets evaluated
+  let stuck = app(Var(0), app(ID.clone(), Var(1)));
+  let mut k = Krivine::new(&stuck);
+  assert!(k.run(100));
+  assert_eq!(k.read_back(), stuck);
+  let mut c = Cek::new(&stuck);
+  assert!(c.run(100));
+  assert_eq!(c.read_back(), app(Var(0), Var(1)));
+}
+
+#[test]
+fn test_nbe() {
+  use crate::lambda::*;
+  use crate::nbe::normalize;
+  use Expr::*;
+  let open = [
+    app(lam(app(Var(0), Var(1))), lam(Var(3))),
+    lam(app(lam(lam(app(Var(1), Var(2)))), Var(5))),
+    app(app(FORK.clone(), SUCC.clone()), Var(2)),
+    lam(app(PLUS.clone(), Var(0))),
+  ];
+  let cases = arithmetic()
+    .map(|(e, _)| e)
+    .into_iter()
+    .chain(combinators());
+  for e in cases.chain(open) {
+    let mut nf = e.clone();
+    nf.nf();
+    assert_eq!(normalize(&e), nf, "{e}");
+    let mut nbe = e.clone();
+    nbe.nf_nbe();
+    assert_eq!(nbe, nf, "{e}");
+  }
+}
+
+#[test]
+fn test_nbe_sharing() {
+  use crate::lambda::*;
+  use crate::machine::Env;
+  use crate::nbe::*;
+  use std::rc::Rc;
+  use Expr::*;
+  // (λx. x x) (I z): the argument is evaluated once, both uses get that value
+  let e = app(lam(app(Var(0), Var(0))), app(ID.clone(), Var(0)));
+  let value = eval(&e, &Env::new());
+  let Value::App(f, arg) = &*value else {
+    panic!()
+  };
+  assert!(matches!(**f, Value::Free(0)));
+  assert!(Rc::ptr_eq(f, &arg.force()));
+  assert!(Rc::ptr_eq(&arg.force(), &arg.force()));
+  assert_eq!(quote(&value, 0), app(Var(0), Var(0)));
+}
+
+#[test]
+fn test_vm() {
+  use crate::lambda::*;
+  use crate::vm::*;
+  use lambda_arena::heap::Heap;
+  use Expr::*;
+  let mut heap : Heap<Obj, 256, 64> = Heap::new();
+  let open = [
+    app(lam(app(Var(0), Var(1))), lam(Var(3))),
+    lam(app(lam(lam(app(Var(1), Hole))), app(Var(0), Var(4)))),
+    Var(300),
+  ];
+  let cases = arithmetic()
+    .map(|(e, _)| e)
+    .into_iter()
+    .chain(combinators());
+  for e in cases.chain(open) {
+    let mut nf = e.clone();
+    nf.nf();
+    let code = Code::compile(&e).unwrap();
+    assert_eq!(code.run(&mut heap, 100000), Ok(nf), "{e}");
+    assert_eq!(heap.verify(), Ok(0));
+  }
+  let omega = app(lam(app(Var(0), Var(0))), lam(app(Var(0), Var(0))));
+  let code = Code::compile(&omega).unwrap();
+  assert_eq!(code.run(&mut heap, 1000), Err(VmError::OutOfFuel));
+}
+
+#[test]
+fn test_vm_large() {
+  use crate::lambda::*;
+  use crate::vm::*;
+  use lambda_arena::heap::Heap;
+  use Expr::*;
+  let mut heap : Heap<Obj, 256, 64> = Heap::new();
+  // an index and an argument too long for a one byte operand, under 201
+  // binders
+  let mut e = app(Var(200), Expr::from_nat(60));
+  for _ in 0..201 {
+    e = lam(e);
+  }
+  let code = Code::compile(&e).unwrap();
+  assert!(code.as_bytes().windows(3).any(|op| op == [0, 0xc8, 0x01]));
+  let code = Code::from_bytes(code.as_bytes()).unwrap();
+  assert_eq!(code.run(&mut heap, 100000), Ok(e));
+  // a free variable past what `U` counts, and code past what it addresses
+  #[cfg(not(feature = "u32-index"))]
+  {
+    let code = Code::compile(&Var(70000)).unwrap();
+    assert_eq!(code.run(&mut heap, 100), Err(VmError::TooLarge));
+    let long = std::vec![3; 65535];
+    assert_eq!(Code::from_bytes(&long), Err(VmError::TooLarge));
+  }
+}
+
+#[test]
+fn test_vm_bytes() {
+  use crate::lambda::*;
+  use crate::vm::*;
+  use Expr::*;
+  // operands are LEB128, seven bits a byte, low bits first
+  let access = |n| Code::compile(&Var(n)).unwrap();
+  assert_eq!(access(0).as_bytes(), [0, 0]);
+  assert_eq!(access(127).as_bytes(), [0, 0x7f]);
+  assert_eq!(access(128).as_bytes(), [0, 0x80, 0x01]);
+  assert_eq!(access(300).as_bytes(), [0, 0xac, 0x02]);
+  assert_eq!(
+    access(u32::MAX).as_bytes(),
+    [0, 0xff, 0xff, 0xff, 0xff, 0x0f]
+  );
+  for n in [0, 1, 127, 128, 300, 16383, 16384, u32::MAX] {
+    assert_eq!(Code::from_bytes(access(n).as_bytes()), Ok(access(n)));
+  }
+  let (e, _) = &arithmetic()[3];
+  let code = Code::compile(e).unwrap();
+  let bytes = code.as_bytes();
+  assert_eq!(Code::from_bytes(bytes), Ok(code.clone()));
+  // cut short anywhere, it either ends on an unfinished op or jumps past the
+  // end
+  for len in 0..bytes.len() {
+    assert!(Code::from_bytes(&bytes[..len]).is_err(), "{len}");
+  }
+  // no such op
+  assert_eq!(Code::from_bytes(&[9]), Err(VmError::BadCode(0)));
+  // an operand that never ends, or does not fit
+  assert_eq!(Code::from_bytes(&[0, 0x80]), Err(VmError::BadCode(0)));
+  let mut long = [0xff; 12];
+  long[0] = 0;
+  long[11] = 0x01;
+  assert_eq!(Code::from_bytes(&long), Err(VmError::BadCode(0)));
+  // falls off the end after binding
+  assert_eq!(Code::from_bytes(&[1]), Err(VmError::BadCode(1)));
+  // pushes code ending in the middle of an op
+  assert_eq!(
+    Code::from_bytes(&[2, 1, 0, 0, 0, 0]),
+    Err(VmError::BadCode(3))
+  );
+  assert_eq!(Code::from_bytes(&[2, 5, 0, 0]), Err(VmError::BadCode(7)));
+}
+
+#[test]
+fn test_ski() {
+  use crate::lambda::*;
+  use crate::ski::*;
+  use lambda_arena::heap::Heap;
+  use Expr::*;
+  let mut heap : Heap<Node, 256, 64> = Heap::new();
+  let cases = arithmetic()
+    .map(|(e, _)| e)
+    .into_iter()
+    .chain(combinators());
+  let cases = cases.chain([Expr::from_nat(1), lam(app(Var(0), Var(3)))]);
+  for e in cases {
+    let mut nf = e.clone();
+    nf.nf();
+    for basis in [Basis::Ski, Basis::Bcw] {
+      let mut ski = Ski::from_expr(&e, basis).unwrap();
+      let mut back = ski.to_expr();
+      back.nf();
+      assert_eq!(back, nf, "{e} {ski}");
+      assert_eq!(ski.nf_in(&mut heap, 100000), Ok(true));
+      let mut back = ski.to_expr();
+      back.nf();
+      assert_eq!(back, nf, "{e} {ski}");
+      assert_eq!(heap.verify(), Ok(0));
+    }
+  }
+  // church 1 is not `I`: that would take eta
+  let one = Ski::from_expr(&Expr::from_nat(1), Basis::Bcw).unwrap();
+  assert_eq!(std::format!("{one}"), "C (B B I) I");
+  let k = Ski::from_expr(&CONST, Basis::Bcw).unwrap();
+  assert_eq!(std::format!("{k}"), "B K I");
+  let k = Ski::from_expr(&CONST, Basis::Ski).unwrap();
+  assert_eq!(std::format!("{k}"), "S (K K) I");
+}
+
+#[test]
+fn test_ski_free() {
+  use crate::lambda::*;
+  use crate::ski::*;
+  use lambda_arena::heap::Heap;
+  use lambda_arena::lambda::U;
+  use std::boxed::Box;
+  use Expr::*;
+  let ap = |l, r| Ski::App(Box::new(l), Box::new(r));
+  let [s, k, i, b] = [Comb::S, Comb::K, Comb::I, Comb::B].map(Ski::Comb);
+  // free variables come out from under the binders they were inside
+  let e = lam(lam(Var(2)));
+  let kk = ap(k.clone(), ap(k.clone(), Ski::Var(0)));
+  assert_eq!(Ski::from_expr(&e, Basis::Ski), Ok(kk));
+  let ski_of = |v| ap(ap(s.clone(), ap(k.clone(), Ski::Var(v))), i.clone());
+  let e = lam(app(Var(3), Var(0)));
+  assert_eq!(Ski::from_expr(&e, Basis::Ski), Ok(ski_of(2)));
+  assert_eq!(
+    Ski::from_expr(&e, Basis::Bcw),
+    Ok(ap(ap(b, Ski::Var(2)), i.clone()))
+  );
+  // the arena keeps `U::MAX` for itself
+  let mut heap : Heap<Node, 256, 64> = Heap::new();
+  let max = u32::from(U::MAX);
+  let mut largest = Ski::Var(max - 1);
+  assert_eq!(largest.nf_in(&mut heap, 10), Ok(true));
+  assert_eq!(largest, Ski::Var(max - 1));
+  assert_eq!(
+    Ski::Var(max).nf_in(&mut heap, 10),
+    Err(ArenaError::VarTooLarge(max))
+  );
+  // but under a binder `U::MAX` is one less once abstracted
+  let e = lam(app(Var(max), Var(0)));
+  let mut ski = Ski::from_expr(&e, Basis::Ski).unwrap();
+  assert_eq!(ski, ski_of(max - 1));
+  assert_eq!(ski.nf_in(&mut heap, 10), Ok(true));
+  assert_eq!(ski, ski_of(max - 1));
+}
+
+#[test]
+fn test_parse() {
+  use crate::lambda::*;
+  use crate::parse::*;
+  use Expr::*;
+  assert_eq!(parse("λx y. x y"), Ok(lam(lam(app(Var(1), Var(0))))));
+  assert_eq!(parse("λ λ ➊ 🄌"), Ok(lam(lam(app(Var(1), Var(0))))));
+  assert_eq!(parse("\\ \\ [1] [0]"), Ok(lam(lam(app(Var(1), Var(0))))));
+  assert_eq!(parse("λx y z. x z (y z)"), Ok(FORK.clone()));
+  assert_eq!(parse("λ. λ. ➊"), Ok(CONST.clone()));
+  assert_eq!(parse("λI. I"), Ok(ID.clone()));
+  // plain numbers are indices under nameless binders, if they are in scope
+  assert_eq!(parse("λ λ 1 0"), Ok(lam(lam(app(Var(1), Var(0))))));
+  assert_eq!(parse("λ 0"), Ok(lam(Var(0))));
+  assert_eq!(parse("λ 1"), Ok(lam(Expr::from_nat(1))));
+  assert_eq!(parse("λx. x 0"), Ok(lam(app(Var(0), Expr::from_nat(0)))));
+  assert_eq!(
+    parse_printed("λ λ 1 0"),
+    Ok(lam(lam(app(Expr::from_nat(1), Expr::from_nat(0)))))
+  );
+  assert_eq!(
+    parse("+ 2 3"),
+    Ok(app(app(PLUS.clone(), Expr::from_nat(2)), Expr::from_nat(3)))
+  );
+  let error = |at, kind| Err(ParseError { at, kind });
+  assert_eq!(parse("f"), error(0, ParseErrorKind::Unbound));
+  assert_eq!(parse("λx. (x"), error(6, ParseErrorKind::Expected(")")));
+  assert_eq!(
+    parse("I)"),
+    error(1, ParseErrorKind::Expected("end of input"))
+  );
+  assert_eq!(parse("λ x. ?"), error(5, ParseErrorKind::Char('?')));
+  assert_eq!(parse(""), error(0, ParseErrorKind::Expected("a term")));
+  assert_eq!(parse("99999999999"), error(0, ParseErrorKind::TooLarge));
+  assert_eq!(parse("[1"), error(2, ParseErrorKind::Expected("]")));
+}
+
+#[test]
+fn test_parse_printed() {
+  use crate::lambda::*;
+  use crate::parse::parse_printed;
+  use Expr::*;
+  let n = Expr::from_nat;
+  let cases = [
+    lam(lam(lam(Var(0)))),
+    lam(lam(n(1))),
+    lam(n(7)),
+    app(CONST.clone(), lam(Var(0))),
+    app(CONST.clone(), lam(n(0))),
+    lam(app(Var(0), n(0))),
+    app(lam(app(Var(0), Var(1))), lam(Var(3))),
+    lam(app(lam(lam(app(Var(1), Hole))), app(Var(0), Var(4)))),
+    app(Var(0), app(Var(1), app(Var(2), Var(30)))),
+    app(app(FORK.clone(), SUCC.clone()), Var(2)),
+    lam(app(PLUS.clone(), Var(0))),
+  ];
+  let cases = cases.into_iter().chain(arithmetic().map(|(e, _)| e));
+  for e in cases.chain(combinators()) {
+    let printed = std::format!("{e}");
+    assert_eq!(parse_printed(&printed), Ok(e.clone()), "{printed}");
+    // free variables get names nothing binds
+    if !e.closed(0) {
+      continue;
+    }
+    let named = DisplayStruct {
+      expr : &e,
+      cursor : &Hole,
+      leaf_mode : LeafMode::No,
+      notation : Notation::Named,
+    };
+    let printed = std::format!("{named}");
+    assert_eq!(parse_printed(&printed), Ok(e.clone()), "{printed}");
+  }
+}
+
+#[test]
+fn test_named() {
+  use crate::lambda::*;
+  use core::fmt::Write;
+  use std::string::String;
+  use Expr::*;
+  let named = |expr : &Expr| {
+    let mut out = String::new();
+    let display = DisplayStruct {
+      expr,
+      cursor : &Hole,
+      leaf_mode : LeafMode::No,
+      notation : Notation::Named,
+    };
+    write!(out, "{display}").map(|()| out)
+  };
+  let show = |e : &Expr| named(e).unwrap();
+  assert_eq!(
+    show(&lam(lam(app(app(Var(1), Var(0)), Var(0))))),
+    "λx.λy.x y y"
+  );
+  assert_eq!(show(&lam(app(Var(0), Var(1)))), "λy.y x");
+  assert_eq!(
+    show(&lam(app(Var(0), app(Var(0), lam(Var(1)))))),
+    "λx.x (x (λy.x))"
+  );
+  assert_eq!(show(&FORK), "S");
+  assert_eq!(show(&lam(Thunk(0))), "λx.«thunk»");
+  assert_eq!(std::format!("{}", lam(Thunk(0))), "λ«thunk»");
+  // names past the largest index, which leaves `u32::MAX + 1` free ones
+  assert_eq!(show(&lam(Var(u32::MAX))), "λx1431655765.z1431655764");
+}
+
+#[test]
+fn test_strategies() {
+  use crate::lambda::*;
+  use crate::strategy::Dir::*;
+  use crate::strategy::*;
+  use Expr::*;
+  let i = |e| app(ID.clone(), e);
+  let found = |path : &[Dir]| StepResult::Contracted(path.to_vec());
+  let done = StepResult::Done;
+  // in the order of `Strategy::ALL`: normal, applicative, by value, by name,
+  // head, weak head
+  let cases = [
+    // (λ. I 0) (I y)
+    (
+      app(lam(i(Var(0))), i(Var(5))),
+      [
+        found(&[]),
+        found(&[Left, Body]),
+        found(&[Right]),
+        found(&[]),
+        found(&[]),
+        found(&[]),
+      ],
+    ),
+    // λ. I 0: the weak ones stop at the lambda
+    (
+      lam(i(Var(0))),
+      [
+        found(&[Body]),
+        found(&[Body]),
+        done.clone(),
+        done.clone(),
+        found(&[Body]),
+        done.clone(),
+      ],
+    ),
+    // y (I z): stuck at the head, with a redex in the argument
+    (
+      app(Var(5), i(Var(6))),
+      [
+        found(&[Right]),
+        found(&[Right]),
+        found(&[Right]),
+        found(&[Right]),
+        done.clone(),
+        done.clone(),
+      ],
+    ),
+  ];
+  for (e, expected) in cases {
+    for (strategy, expected) in Strategy::ALL.into_iter().zip(expected) {
+      let mut stepped = e.clone();
+      let path = e.redex_path(strategy);
+      assert_eq!(stepped.step(strategy), expected, "{strategy} {e}");
+      let StepResult::Contracted(at) = expected else {
+        assert_eq!(stepped, e);
+        assert_eq!(path, None);
+        continue;
+      };
+      assert_eq!(path.as_ref(), Some(&at));
+      let mut redex = e.clone();
+      assert!(redex.at_path(&at).unwrap().beta(), "{strategy} {e}");
+      assert_eq!(stepped, redex);
+    }
+  }
+}
+
+#[test]
+fn test_reduce() {
+  use crate::lambda::*;
+  use crate::strategy::*;
+  use Expr::*;
+  let unlimited = Budget::default();
+  let w = lam(app(Var(0), Var(0)));
+  let omega = app(w.clone(), w);
+  for strategy in Strategy::ALL {
+    let mut e = omega.clone();
+    let outcome = e.reduce(strategy, unlimited);
+    assert_eq!(
+      outcome,
+      Outcome::Looping { at : 1, period : 1 },
+      "{strategy}"
+    );
+  }
+  // (λx. x x x) (λx. x x x) only grows
+  let w3 = lam(app(app(Var(0), Var(0)), Var(0)));
+  let grows = app(w3.clone(), w3);
+  let budget = Budget {
+    steps : None,
+    nodes : Some(100),
+  };
+  let mut e = grows.clone();
+  assert_eq!(
+    e.reduce(Strategy::NormalOrder, budget),
+    Outcome::OutOfMemory
+  );
+  assert!(e.size() > 100);
+  let budget = Budget {
+    steps : Some(5),
+    nodes : None,
+  };
+  let mut e = grows;
+  assert_eq!(e.reduce(Strategy::NormalOrder, budget), Outcome::OutOfFuel);
+  for (e, n) in arithmetic() {
+    for strategy in [Strategy::NormalOrder, Strategy::ApplicativeOrder] {
+      let mut e = e.clone();
+      assert!(matches!(e.reduce(strategy, unlimited), Outcome::Normal(_)));
+      assert_eq!(e.to_nat(), Some(n));
+    }
+  }
+  // counting the steps it took
+  let mut e = app(ID.clone(), app(ID.clone(), Var(0)));
+  assert_eq!(
+    e.reduce(Strategy::NormalOrder, unlimited),
+    Outcome::Normal(2)
+  );
+  // a budget of exactly the steps needed is enough, one fewer is not
+  let exactly = |steps| Budget {
+    steps : Some(steps),
+    nodes : None,
+  };
+  let mut e = app(ID.clone(), app(ID.clone(), Var(0)));
+  assert_eq!(
+    e.reduce(Strategy::NormalOrder, exactly(2)),
+    Outcome::Normal(2)
+  );
+  let mut e = app(ID.clone(), app(ID.clone(), Var(0)));
+  assert_eq!(
+    e.reduce(Strategy::NormalOrder, exactly(1)),
+    Outcome::OutOfFuel
+  );
+  assert_eq!(e, app(ID.clone(), Var(0)));
+  let mut e = ID.clone();
+  assert_eq!(
+    e.reduce(Strategy::NormalOrder, exactly(0)),
+    Outcome::Normal(0)
+  );
+  // K I Ω: by name drops Ω, by value gets stuck in it
+  let kio = app(app(CONST.clone(), ID.clone()), omega);
+  let mut e = kio.clone();
+  assert_eq!(
+    e.reduce(Strategy::CallByName, unlimited),
+    Outcome::Normal(2)
+  );
+  assert_eq!(e, *ID);
+  let mut e = kio;
+  let outcome = e.reduce(Strategy::CallByValue, unlimited);
+  assert!(matches!(outcome, Outcome::Looping { period : 1, .. }));
+}
+
+#[test]
+fn test_strategy_outcomes() {
+  use crate::lambda::*;
+  use crate::strategy::*;
+  use Expr::*;
+  let w = lam(app(Var(0), Var(0)));
+  let omega = app(w.clone(), w);
+  // (λx y. y) Ω: only the strategies that reduce arguments first loop
+  let e = app(lam(lam(Var(0))), omega);
+  for strategy in Strategy::ALL {
+    let mut reduced = e.clone();
+    let outcome = reduced.reduce(strategy, Budget::default());
+    if matches!(strategy, Strategy::ApplicativeOrder | Strategy::CallByValue) {
+      assert_eq!(
+        outcome,
+        Outcome::Looping { at : 1, period : 1 },
+        "{strategy}"
+      );
+      assert_eq!(reduced, e, "{strategy}");
+    } else {
+      assert_eq!(outcome, Outcome::Normal(1), "{strategy}");
+      assert_eq!(reduced, *ID, "{strategy}");
+    }
+  }
+  // a path that does not fit the term leads nowhere
+  let mut e = e;
+  assert_eq!(e.at_path(&[Dir::Body]), None);
+  assert_eq!(e.at_path(&[Dir::Left, Dir::Left]), None);
+  assert_eq!(e.at_path(&[Dir::Left, Dir::Body]), Some(&mut ID.clone()));
+}
+
+#[test]
+fn test_history() {
+  use crate::history::History;
+  use crate::strategy::*;
+  use std::vec::Vec;
+  let (start, n) = arithmetic()[3].clone();
+  let mut terms = Vec::from([start.clone()]);
+  let mut history = History::new(start.clone(), usize::MAX);
+  while history.step(Strategy::NormalOrder) != StepResult::Done {
+    terms.push(history.term().clone());
+  }
+  let last = terms.len() - 1;
+  assert_eq!(
+    (history.first(), history.at(), history.last()),
+    (0, last, last)
+  );
+  assert_eq!(history.term().to_nat(), Some(n));
+  // all the way back and forth again
+  while history.back() {
+    assert_eq!(history.term(), &terms[history.at()]);
+  }
+  assert_eq!(history.at(), 0);
+  while history.forward() {
+    assert_eq!(history.term(), &terms[history.at()]);
+  }
+  assert_eq!(history.at(), last);
+  // back then forward is where it was
+  assert!(history.jump(3));
+  assert!(history.back());
+  assert!(history.forward());
+  assert_eq!(history.term(), &terms[3]);
+  assert_eq!(history.at(), 3);
+  // nowhere to jump past either end
+  assert!(!history.jump(last + 1));
+  assert_eq!(history.at(), 3);
+  assert_eq!(history.term(), &terms[3]);
+  // a step from the middle drops what was ahead
+  assert!(history.back());
+  let taken = history.step(Strategy::ApplicativeOrder);
+  assert!(matches!(taken, StepResult::Contracted(_)));
+  assert_eq!((history.at(), history.last()), (3, 3));
+  assert!(!history.forward());
+  assert!(history.back());
+  assert_eq!(history.term(), &terms[2]);
+}
+
+#[test]
+fn test_history_limit() {
+  use crate::history::History;
+  use crate::strategy::*;
+  use std::vec::Vec;
+  let (start, _) = arithmetic()[3].clone();
+  let mut terms = Vec::from([start.clone()]);
+  let mut history = History::new(start, 40);
+  while history.step(Strategy::NormalOrder) != StepResult::Done {
+    terms.push(history.term().clone());
+    assert_eq!(history.at(), terms.len() - 1);
+    assert_eq!(history.last(), history.at());
+  }
+  let first = history.first();
+  assert!(first > 0);
+  assert!(history.jump(first));
+  assert_eq!(history.term(), &terms[first]);
+  assert!(!history.back());
+  assert!(!history.jump(first - 1));
+  assert_eq!(history.at(), first);
+  assert!(history.jump(terms.len() - 1));
+  assert_eq!(history.term(), terms.last().unwrap());
+  // nothing fits, but the term still gets stepped
+  let mut history = History::new(arithmetic()[0].0.clone(), 0);
+  assert!(matches!(
+    history.step(Strategy::NormalOrder),
+    StepResult::Contracted(_)
+  ));
+  assert_eq!((history.first(), history.at(), history.last()), (1, 1, 1));
+  assert!(!history.back());
+}
@@ -0,0 +1,487 @@
+//! bytecode for `Expr`, and a lazy Krivine style machine running it in a
+//! `Heap`, for definitions that get evaluated over and over: compiling once
+//! leaves nothing to traverse or copy but the arguments the code builds.
+//!
+//! code is a byte string, so it can be stored as is. an application pushes
+//! its argument as a thunk and goes on with the function, a lambda binds the
+//! next argument, a variable jumps into whatever it is bound to. thunks are
+//! overwritten with their value the first time they are evaluated. reading
+//! the result back applies closures to fresh variables and keeps running,
+//! which gives the normal form.
+extern crate alloc;
+use alloc::vec::Vec;
+use core::prelude::rust_2024::*;
+use core::{fmt, write};
+use lambda_arena::heap::{Heap, HeapError, Idx, Object, Root};
+use lambda_arena::lambda::U;
+
+use crate::lambda::{app, lam, Expr};
+
+// opcodes, operands follow in LEB128
+/// `n`: go on with the `n`th binding
+const ACCESS : u8 = 0;
+/// bind the next argument, or stop at a closure if there is none
+const GRAB : u8 = 1;
+/// `len`: push the code after the next `len` bytes as an argument, go on
+const PUSH : u8 = 2;
+/// a `Hole`
+const HOLE : u8 = 3;
+
+/// how many arguments and pending updates the machine keeps at most.
+pub const STACK : usize = 1024;
+
+/// why compiling, loading or running code failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+  Heap(HeapError),
+  /// the editor cursor, `replace_slot` it before compiling
+  Slot,
+  /// only ever exists in the middle of `beta`
+  Thunk,
+  /// code longer than `U` can address, or a variable past what it can count
+  TooLarge,
+  /// not valid code, at this byte offset
+  BadCode(usize),
+  /// more than `STACK` arguments or updates pending
+  StackOverflow,
+  /// ran for the number of steps it was given
+  OutOfFuel,
+}
+
+impl From<HeapError> for VmError {
+  fn from(value : HeapError) -> Self { VmError::Heap(value) }
+}
+
+impl fmt::Display for VmError {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      VmError::Heap(e) => write!(f, "{e}"),
+      VmError::Slot => write!(f, "unfilled slot"),
+      VmError::Thunk => write!(f, "stray thunk"),
+      VmError::TooLarge => write!(f, "too large"),
+      VmError::BadCode(at) => write!(f, "bad code at {at}"),
+      VmError::StackOverflow => write!(f, "stack overflow"),
+      VmError::OutOfFuel => write!(f, "out of fuel"),
+    }
+  }
+}
+
+/// compiled `Expr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Code(Vec<u8>);
+
+fn put(out : &mut Vec<u8>, mut n : usize) {
+  while n >= 0x80 {
+    out.push(n as u8 | 0x80);
+    n >>= 7;
+  }
+  out.push(n as u8);
+}
+
+/// the operand at `at` and the offset right after it.
+fn get(bytes : &[u8], mut at : usize) -> Option<(usize, usize)> {
+  let mut n = 0usize;
+  for shift in (0..usize::BITS).step_by(7) {
+    let byte = *bytes.get(at)?;
+    at += 1;
+    n |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+    if byte < 0x80 {
+      return Some((n, at));
+    }
+  }
+  None
+}
+
+impl Code {
+  pub fn compile(expr : &Expr) -> Result<Code, VmError> {
+    fn compile_(expr : &Expr, out : &mut Vec<u8>) -> Result<(), VmError> {
+      match expr {
+        Expr::Var(n) => {
+          out.push(ACCESS);
+          put(out, *n as usize);
+        }
+        Expr::Lam(e) => {
+          out.push(GRAB);
+          compile_(e, out)?;
+        }
+        Expr::App(l, r) => {
+          let mut left = Vec::new();
+          compile_(l, &mut left)?;
+          out.push(PUSH);
+          put(out, left.len());
+          out.extend(left);
+          compile_(r, out)?;
+        }
+        Expr::Hole => out.push(HOLE),
+        Expr::Slot => return Err(VmError::Slot),
+        Expr::Thunk(_) => return Err(VmError::Thunk),
+      }
+      Ok(())
+    }
+    let mut out = Vec::new();
+    compile_(expr, &mut out)?;
+    if out.len() >= U::MAX as usize {
+      return Err(VmError::TooLarge);
+    }
+    Ok(Code(out))
+  }
+
+  #[must_use]
+  pub fn as_bytes(&self) -> &[u8] { &self.0 }
+
+  /// load code stored with `as_bytes`. everything the machine could run into
+  /// is checked here, so the machine itself does not have to.
+  pub fn from_bytes(bytes : &[u8]) -> Result<Code, VmError> {
+    if bytes.len() >= U::MAX as usize {
+      return Err(VmError::TooLarge);
+    }
+    let mut starts = Vec::new();
+    let mut targets = Vec::new();
+    // whether the op before `at` carries on into it
+    let mut falls_through = true;
+    let mut at = 0;
+    while at < bytes.len() {
+      starts.push(at);
+      let bad = VmError::BadCode(at);
+      falls_through = match bytes[at] {
+        ACCESS => {
+          at = get(bytes, at + 1).ok_or(bad)?.1;
+          false
+        }
+        GRAB => {
+          at += 1;
+          true
+        }
+        PUSH => {
+          let (len, next) = get(bytes, at + 1).ok_or(bad)?;
+          at = next;
+          targets.push(next.checked_add(len).ok_or(bad)?);
+          true
+        }
+        HOLE => {
+          at += 1;
+          false
+        }
+        _ => return Err(bad),
+      };
+    }
+    if falls_through {
+      return Err(VmError::BadCode(at));
+    }
+    if let Some(&target) = targets.iter().find(|t| starts.binary_search(t).is_err()) {
+      return Err(VmError::BadCode(target));
+    }
+    Ok(Code(bytes.to_vec()))
+  }
+
+  fn op(&self, pc : usize) -> (u8, usize, usize) {
+    match self.0[pc] {
+      op @ (ACCESS | PUSH) => {
+        let (n, next) = get(&self.0, pc + 1).unwrap();
+        (op, n, next)
+      }
+      op => (op, 0, pc + 1),
+    }
+  }
+}
+
+/// what the machine keeps in a `Heap`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Obj(u8, U, U);
+
+#[derive(Debug, Default)]
+pub enum ObjRepr {
+  #[default]
+  Hole,
+  /// a binding, and the rest of the environment (0 past the outermost one)
+  Env(Idx<Obj>, Idx<Obj>),
+  /// code and its environment: a lambda, or an argument not evaluated yet
+  Clos(U, Idx<Obj>),
+  /// an evaluated argument, standing for its value
+  Ind(Idx<Obj>),
+  /// a variable bound while reading back, by de Bruijn level
+  Level(U),
+  /// de Bruijn index of a variable free in the whole term
+  Free(U),
+  /// stuck: the left side is never a `Clos`
+  App(Idx<Obj>, Idx<Obj>),
+}
+
+impl From<ObjRepr> for Obj {
+  fn from(value : ObjRepr) -> Self {
+    match value {
+      ObjRepr::Hole => Obj(0, 0, 0),
+      ObjRepr::Env(value, next) => Obj(1, value.raw, next.raw),
+      ObjRepr::Clos(pc, env) => Obj(2, pc, env.raw),
+      ObjRepr::Ind(to) => Obj(3, to.raw, 0),
+      ObjRepr::Level(l) => Obj(4, l, 0),
+      ObjRepr::Free(i) => Obj(5, i, 0),
+      ObjRepr::App(l, r) => Obj(6, l.raw, r.raw),
+    }
+  }
+}
+
+impl From<Obj> for ObjRepr {
+  fn from(value : Obj) -> Self {
+    match value {
+      Obj(1, value, next) => ObjRepr::Env(value.into(), next.into()),
+      Obj(2, pc, env) => ObjRepr::Clos(pc, env.into()),
+      Obj(3, to, _) => ObjRepr::Ind(to.into()),
+      Obj(4, l, _) => ObjRepr::Level(l),
+      Obj(5, i, _) => ObjRepr::Free(i),
+      Obj(6, l, r) => ObjRepr::App(l.into(), r.into()),
+      _ => ObjRepr::Hole,
+    }
+  }
+}
+
+impl Object for Obj {
+  fn visit_refs(&self, f : &mut dyn FnMut(Idx<Self>)) {
+    match ObjRepr::from(*self) {
+      ObjRepr::Env(value, next) => {
+        f(value);
+        if next != 0.into() {
+          f(next);
+        }
+      }
+      ObjRepr::Clos(_, env) if env != 0.into() => f(env),
+      ObjRepr::Ind(to) => f(to),
+      ObjRepr::App(l, r) => {
+        f(l);
+        f(r);
+      }
+      _ => {}
+    }
+  }
+  fn map_refs(&mut self, f : &mut dyn FnMut(Idx<Self>) -> Idx<Self>) {
+    let mut f = |r : Idx<Self>| if r == 0.into() { r } else { f(r) };
+    *self = match ObjRepr::from(*self) {
+      ObjRepr::Env(value, next) => ObjRepr::Env(f(value), f(next)),
+      ObjRepr::Clos(pc, env) => ObjRepr::Clos(pc, f(env)),
+      ObjRepr::Ind(to) => ObjRepr::Ind(f(to)),
+      ObjRepr::App(l, r) => ObjRepr::App(f(l), f(r)),
+      o => o,
+    }
+    .into();
+  }
+  fn moved(to : Idx<Self>) -> Self { ObjRepr::Ind(to).into() }
+  fn moved_to(&self) -> Idx<Self> { self.1.into() }
+}
+
+impl fmt::Debug for Obj {
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", ObjRepr::from(*self))
+  }
+}
+
+// tags of the machine's frames on the root stack
+/// an argument waiting for a lambda
+const ARG : U = 0;
+/// a thunk being evaluated, to be overwritten with its value
+const UPDATE : U = 1;
+
+/// a running machine: the code, the heap, what is left of its fuel.
+struct Run<'a, const PAGESIZE: usize, const G1PAGES: usize> {
+  code : &'a Code,
+  heap : &'a mut Heap<Obj, PAGESIZE, G1PAGES>,
+  fuel : usize,
+}
+
+impl<const PAGESIZE: usize, const G1PAGES: usize> Run<'_, PAGESIZE, G1PAGES> {
+  fn repr(&self, at : Idx<Obj>) -> Result<ObjRepr, VmError> {
+    Ok(ObjRepr::from(*self.heap.get(at)?))
+  }
+  fn alloc(&mut self, repr : ObjRepr) -> Result<Idx<Obj>, VmError> {
+    Ok(self.heap.init(repr.into())?)
+  }
+  fn follow(&self, mut at : Idx<Obj>) -> Result<Idx<Obj>, VmError> {
+    while let ObjRepr::Ind(to) = self.repr(at)? {
+      at = to;
+    }
+    Ok(at)
+  }
+
+  fn push(&mut self, depth : &mut usize, at : Idx<Obj>, tag : U) -> Result<(), VmError> {
+    if *depth == STACK {
+      return Err(VmError::StackOverflow);
+    }
+    self.heap.push_frame(at, tag)?;
+    *depth += 1;
+    Ok(())
+  }
+
+  /// run from `pc` with the environment rooted at `env`, the frames above
+  /// `base` as the stack, `depth` of them, until it stops with nothing left
+  /// on it. returns the value, unrooted.
+  fn eval(
+    &mut self,
+    mut pc : usize,
+    env : Root,
+    base : Root,
+    mut depth : usize,
+  ) -> Result<Idx<Obj>, VmError> {
+    loop {
+      self.fuel = self.fuel.checked_sub(1).ok_or(VmError::OutOfFuel)?;
+      let (op, n, next) = self.code.op(pc);
+      let mut value = match op {
+        ACCESS => {
+          let (mut at, mut i) = (self.heap.rooted(env), n);
+          while i > 0 && at != 0.into() {
+            let ObjRepr::Env(_, rest) = self.repr(at)? else {
+              unreachable!()
+            };
+            (at, i) = (rest, i - 1);
+          }
+          if at == 0.into() {
+            let free = U::try_from(i).map_err(|_| VmError::TooLarge)?;
+            self.alloc(ObjRepr::Free(free))?
+          } else {
+            let ObjRepr::Env(bound, _) = self.repr(at)? else {
+              unreachable!()
+            };
+            let bound = self.follow(bound)?;
+            match self.repr(bound)? {
+              ObjRepr::Clos(to, to_env) => {
+                if self.code.0[to as usize] != GRAB {
+                  self.push(&mut depth, bound, UPDATE)?;
+                }
+                pc = to as usize;
+                self.heap.set_root(env, to_env);
+                continue;
+              }
+              _ => bound,
+            }
+          }
+        }
+        GRAB => match self.heap.pop_frame(base) {
+          Some((arg, ARG)) => {
+            depth -= 1;
+            let new = self.alloc(ObjRepr::Env(arg, self.heap.rooted(env)))?;
+            self.heap.set_root(env, new);
+            pc = next;
+            continue;
+          }
+          frame => {
+            if let Some((at, tag)) = frame {
+              self.heap.push_frame(at, tag)?;
+            }
+            let pc = U::try_from(pc).unwrap();
+            self.alloc(ObjRepr::Clos(pc, self.heap.rooted(env)))?
+          }
+        },
+        PUSH => {
+          let to = U::try_from(next + n).unwrap();
+          let arg = self.alloc(ObjRepr::Clos(to, self.heap.rooted(env)))?;
+          self.push(&mut depth, arg, ARG)?;
+          pc = next;
+          continue;
+        }
+        _ => self.alloc(ObjRepr::Hole)?,
+      };
+      // hand `value` to the frames until one of them has more to run
+      loop {
+        match self.heap.pop_frame(base) {
+          None => return Ok(value),
+          Some((at, UPDATE)) => {
+            depth -= 1;
+            self.heap.set(at, ObjRepr::Ind(value).into())?;
+          }
+          Some((arg, _)) => match self.repr(value)? {
+            ObjRepr::Clos(to, to_env) => {
+              self.heap.push_frame(arg, ARG)?;
+              pc = to as usize;
+              self.heap.set_root(env, to_env);
+              break;
+            }
+            _ => {
+              depth -= 1;
+              value = self.alloc(ObjRepr::App(value, arg))?;
+            }
+          },
+        }
+      }
+    }
+  }
+
+  /// the value of the thunk at `at`, evaluating it if it was not yet.
+  fn force(&mut self, at : Idx<Obj>) -> Result<Idx<Obj>, VmError> {
+    let at = self.follow(at)?;
+    let ObjRepr::Clos(pc, env) = self.repr(at)? else {
+      return Ok(at);
+    };
+    if self.code.0[pc as usize] == GRAB {
+      return Ok(at);
+    }
+    let env = self.heap.root(env)?;
+    let base = self.heap.root_mark();
+    self.heap.push_frame(at, UPDATE)?;
+    let ret = self.eval(pc as usize, env, base, 1);
+    self.heap.unroot(env);
+    ret
+  }
+
+  /// the normal form of the value at `at`, under `depth` binders. loops
+  /// down right sides, numerals nest that way.
+  fn quote(&mut self, mut at : Idx<Obj>, depth : u32) -> Result<Expr, VmError> {
+    // what the result is the argument of, innermost last
+    let mut funs = Vec::new();
+    let mut ret = loop {
+      at = self.follow(at)?;
+      match self.repr(at)? {
+        ObjRepr::Clos(..) => {
+          let clos = self.heap.root(at)?;
+          let level = U::try_from(depth).map_err(|_| VmError::TooLarge)?;
+          let var = self.alloc(ObjRepr::Level(level))?;
+          let ObjRepr::Clos(pc, env) = self.repr(self.heap.rooted(clos))? else {
+            unreachable!()
+          };
+          self.heap.set_root(clos, env);
+          let base = self.heap.root_mark();
+          self.heap.push_frame(var, ARG)?;
+          let body = self.eval(pc as usize, clos, base, 1);
+          self.heap.unroot(clos);
+          break lam(self.quote(body?, depth + 1)?);
+        }
+        ObjRepr::Level(l) => break Expr::Var(depth - 1 - u32::from(l)),
+        ObjRepr::Free(i) => break Expr::Var(u32::from(i) + depth),
+        ObjRepr::App(l, r) => {
+          let r = self.heap.root(r)?;
+          let l = self.quote(l, depth);
+          let arg = self.heap.rooted(r);
+          self.heap.unroot(r);
+          funs.push(l?);
+          at = self.force(arg)?;
+        }
+        _ => break Expr::Hole,
+      }
+    };
+    while let Some(l) = funs.pop() {
+      ret = app(l, ret);
+    }
+    Ok(ret)
+  }
+}
+
+impl Code {
+  /// the normal form of the compiled term, computed in `heap` within `fuel`
+  /// machine steps. `heap` is left with garbage only, nothing stays rooted.
+  pub fn run<const PAGESIZE: usize, const G1PAGES: usize>(
+    &self,
+    heap : &mut Heap<Obj, PAGESIZE, G1PAGES>,
+    fuel : usize,
+  ) -> Result<Expr, VmError> {
+    let mark = heap.root_mark();
+    let env = heap.root(0.into())?;
+    let base = heap.root_mark();
+    let mut run = Run {
+      code : self,
+      heap,
+      fuel,
+    };
+    let ret = run
+      .eval(0, env, base, 0)
+      .and_then(|value| run.quote(value, 0));
+    run.heap.unroot(mark);
+    ret
+  }
+}
@@ -22,7 +22,7 @@ use hal::Spi;
 use nb::block;
 use rp_pico::hal;
 
-use crate::lambda::DisplayStruct;
+use lambda_calc::lambda::DisplayStruct;
 
 pub struct Lcd<P0, P1, P2, S0, V>
 where
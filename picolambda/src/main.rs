@@ -4,7 +4,6 @@
 
 extern crate alloc;
 
-pub mod lambda;
 pub mod lcd;
 
 use alloc_cortex_m::CortexMHeap;
@@ -36,10 +35,10 @@ use hal::{
   watchdog::Watchdog,
 };
 
-use crate::lambda::Expr;
-use crate::lambda::Expr::*;
-use crate::lambda::LeafMode;
-use crate::lambda::{app, lam, PLUS, POWER, TIMES};
+use lambda_calc::lambda::Expr;
+use lambda_calc::lambda::Expr::*;
+use lambda_calc::lambda::LeafMode;
+use lambda_calc::lambda::{app, lam, PLUS, POWER, TIMES};
 use lambda_arena::*;
 
 #[entry]
@@ -364,7 +363,8 @@ fn main() -> ! {
   //     lambda::DisplayStruct {
   //       expr : &expr,
   //       cursor : &cursor,
-  //       leaf_mode
+  //       leaf_mode,
+  //       notation : lambda::Notation::DeBruijn,
   //     }
   //   )
   //   .unwrap();